@@ -4,7 +4,7 @@ use anyhow::Context;
 
 #[tokio::main]
 pub async fn main() -> anyhow::Result<()> {
-    println!("cargo::rerun-if-changed=migration.sql");
+    println!("cargo::rerun-if-changed=migrations");
     let cargo_target_dir =
         std::env::var("OUT_DIR").context("Failed to get cargo target directory")?;
 
@@ -22,10 +22,57 @@ pub async fn main() -> anyhow::Result<()> {
         .await
         .context("Failed to create database layer")?;
 
-    sqlx::raw_sql(include_str!("./migration.sql"))
-        .execute(&db)
+    // Migrations are applied in order of their file names, which start with their version
+    let mut migrations = std::fs::read_dir("migrations")
+        .context("Failed to read migrations directory")?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read migrations directory")?;
+    migrations.retain(|p| p.extension().is_some_and(|ext| ext == "sql"));
+    migrations.sort();
+
+    // The list of migrations which the server applies is generated from the same files, so that
+    // a migration cannot be added to one and not the other
+    let manifest_dir =
+        std::env::var("CARGO_MANIFEST_DIR").context("Failed to get manifest directory")?;
+    let mut list = String::from("&[\n");
+    for (i, migration) in migrations.iter().enumerate() {
+        let name = migration
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .with_context(|| format!("Invalid migration name {}", migration.display()))?;
+        let version = name
+            .split_once('_')
+            .and_then(|(version, _)| version.parse::<i64>().ok())
+            .with_context(|| format!("Migration {} does not start with its version", name))?;
+        anyhow::ensure!(
+            version == i as i64 + 1,
+            "Migration {} should have version {}",
+            name,
+            i + 1
+        );
+        let file = Path::new(&manifest_dir).join(migration);
+        list.push_str(&format!(
+            "    Migration {{ version: {}, name: {:?}, sql: include_str!({:?}) }},\n",
+            version,
+            name,
+            file.to_str()
+                .context("Migration path must be valid UTF-8")?
+        ));
+
+        let sql = tokio::fs::read_to_string(migration)
+            .await
+            .with_context(|| format!("Failed to read {}", migration.display()))?;
+        sqlx::raw_sql(&sql)
+            .execute(&db)
+            .await
+            .with_context(|| format!("Failed to execute migration {}", migration.display()))?;
+    }
+    list.push(']');
+
+    tokio::fs::write(Path::new(&cargo_target_dir).join("migrations.rs"), list)
         .await
-        .context("Failed to execute migration")?;
+        .context("Failed to write migration list")?;
 
     Ok(())
 }
@@ -1,3 +1,6 @@
+-- NOTE: These use `IF NOT EXISTS` since databases created before migrations were versioned
+-- already contain these tables.
+
 CREATE TABLE IF NOT EXISTS users (
   id VARCHAR(32) NOT NULL PRIMARY KEY,
   username VARCHAR(32) NOT NULL UNIQUE,
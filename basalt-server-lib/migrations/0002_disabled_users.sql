@@ -0,0 +1,4 @@
+-- Accounts which have been removed from the config and may no longer log in
+CREATE TABLE disabled_users (
+    user_id VARCHAR(32) NOT NULL PRIMARY KEY REFERENCES users(id)
+);
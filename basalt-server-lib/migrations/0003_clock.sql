@@ -0,0 +1,12 @@
+-- State of the competition clock.  There is only ever a single row.
+CREATE TABLE clock (
+    id INTEGER NOT NULL PRIMARY KEY CHECK (id = 0),
+    start_time INTEGER NOT NULL, -- milliseconds since the unix epoch
+    total_time_paused INTEGER NOT NULL DEFAULT 0 -- milliseconds
+);
+
+-- Each time that the competition clock was paused
+CREATE TABLE clock_pauses (
+    paused_at INTEGER NOT NULL PRIMARY KEY, -- milliseconds since the unix epoch
+    resumed_at INTEGER -- milliseconds since the unix epoch, NULL while still paused
+);
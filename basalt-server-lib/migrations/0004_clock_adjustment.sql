@@ -0,0 +1,2 @@
+-- Time added to the time limit by hosts, in milliseconds.  Negative if time was removed.
+ALTER TABLE clock ADD COLUMN time_adjustment INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,5 @@
+-- Time given to individual teams on top of the time limit
+CREATE TABLE team_time_offsets (
+    user_id VARCHAR(32) NOT NULL PRIMARY KEY REFERENCES users(id),
+    time_offset INTEGER NOT NULL -- milliseconds, negative if time was removed
+);
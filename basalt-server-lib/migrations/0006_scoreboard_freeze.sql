@@ -0,0 +1,3 @@
+-- Time at which the scoreboard was frozen, in milliseconds since the unix epoch.  NULL while the
+-- scoreboard is live.
+ALTER TABLE clock ADD COLUMN frozen_at INTEGER;
@@ -0,0 +1,5 @@
+-- Submissions made while the scoreboard was frozen which have been revealed by the resolver
+CREATE TABLE revealed_submissions (
+    submission_id VARCHAR(32) NOT NULL PRIMARY KEY REFERENCES submission_history(id),
+    revealed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,5 @@
+-- Problems which were hidden until they were released, either on a schedule or by a host
+CREATE TABLE released_problems (
+    question_index INTEGER NOT NULL PRIMARY KEY,
+    released_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
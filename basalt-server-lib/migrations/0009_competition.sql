@@ -0,0 +1,7 @@
+-- Details of the competition, recorded when the database is created.  There is only ever a single
+-- row, which is missing for databases created before this table existed.
+CREATE TABLE competition (
+    id INTEGER NOT NULL PRIMARY KEY CHECK (id = 0),
+    title TEXT NOT NULL,
+    created_at INTEGER NOT NULL -- milliseconds since the unix epoch
+);
@@ -0,0 +1,5 @@
+-- Teams which have solved every problem, so that they are only announced once
+CREATE TABLE completions (
+    user_id VARCHAR(32) NOT NULL PRIMARY KEY REFERENCES users(id),
+    completed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
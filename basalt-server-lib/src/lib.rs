@@ -2,6 +2,7 @@ pub mod extractors;
 pub mod repositories;
 pub mod server;
 mod services;
+pub mod settings;
 pub mod storage;
 mod utils;
 
@@ -91,6 +91,18 @@ pub async fn delete_announcement(
     .context("Failed to create submission history")
 }
 
+/// Delete all announcements sent by a user.  Returns the number of announcements deleted.
+pub async fn delete_user_announcements(
+    db: impl Executor<'_, Database = Sqlite>,
+    sender: &UserId,
+) -> anyhow::Result<u64> {
+    sqlx::query!("DELETE FROM announcements WHERE sender = ?", sender)
+        .execute(db)
+        .await
+        .context("Failed to delete announcements")
+        .map(|r| r.rows_affected())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
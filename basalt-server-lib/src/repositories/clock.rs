@@ -0,0 +1,260 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, TimeDelta, Utc};
+use sqlx::SqliteExecutor;
+
+use crate::{repositories::users::UserId, server::clock::ClockInfo};
+
+fn from_millis(millis: i64) -> anyhow::Result<DateTime<Utc>> {
+    DateTime::from_timestamp_millis(millis).context("Stored clock time is out of range")
+}
+
+/// Load the clock as it was last saved, if it has ever been saved
+pub async fn get_clock(db: impl SqliteExecutor<'_> + Copy) -> anyhow::Result<Option<ClockInfo>> {
+    let Some(clock) = sqlx::query!(
+        "SELECT start_time, total_time_paused, time_adjustment, frozen_at FROM clock WHERE id = 0"
+    )
+    .fetch_optional(db)
+    .await
+    .context("Failed to get clock")?
+    else {
+        return Ok(None);
+    };
+
+    let pause_time = sqlx::query_scalar!(
+        "SELECT paused_at FROM clock_pauses WHERE resumed_at IS NULL ORDER BY paused_at DESC LIMIT 1"
+    )
+    .fetch_optional(db)
+    .await
+    .context("Failed to get clock pauses")?
+    .map(from_millis)
+    .transpose()?;
+
+    let team_offsets =
+        sqlx::query!(r#"SELECT user_id as "user_id: UserId", time_offset FROM team_time_offsets"#)
+            .fetch_all(db)
+            .await
+            .context("Failed to get team time offsets")?
+            .into_iter()
+            .map(|row| (row.user_id, TimeDelta::milliseconds(row.time_offset)))
+            .collect();
+
+    Ok(Some(ClockInfo {
+        start_time: from_millis(clock.start_time)?,
+        pause_time,
+        total_time_paused: Duration::from_millis(clock.total_time_paused as u64),
+        time_adjustment: TimeDelta::milliseconds(clock.time_adjustment),
+        team_offsets,
+        frozen_at: clock.frozen_at.map(from_millis).transpose()?,
+    }))
+}
+
+/// Save a clock which has just been created, replacing any clock that was saved before
+pub async fn create_clock(
+    db: impl SqliteExecutor<'_> + Copy,
+    clock: &ClockInfo,
+) -> anyhow::Result<()> {
+    let start_time = clock.start_time.timestamp_millis();
+    let total_time_paused = clock.total_time_paused.as_millis() as i64;
+    let time_adjustment = clock.time_adjustment.num_milliseconds();
+    let frozen_at = clock.frozen_at.map(|t| t.timestamp_millis());
+    sqlx::query!(
+        r#"
+        INSERT INTO clock (id, start_time, total_time_paused, time_adjustment, frozen_at)
+        VALUES (0, ?, ?, ?, ?)
+        ON CONFLICT (id) DO UPDATE SET
+            start_time = excluded.start_time,
+            total_time_paused = excluded.total_time_paused,
+            time_adjustment = excluded.time_adjustment,
+            frozen_at = excluded.frozen_at
+        "#,
+        start_time,
+        total_time_paused,
+        time_adjustment,
+        frozen_at,
+    )
+    .execute(db)
+    .await
+    .context("Failed to save clock")?;
+
+    sqlx::query!("DELETE FROM clock_pauses")
+        .execute(db)
+        .await
+        .context("Failed to clear clock pauses")?;
+
+    if let Some(pause_time) = clock.pause_time {
+        record_pause(db, pause_time).await?;
+    }
+
+    sqlx::query!("DELETE FROM team_time_offsets")
+        .execute(db)
+        .await
+        .context("Failed to clear team time offsets")?;
+
+    for (id, offset) in &clock.team_offsets {
+        set_team_offset(db, id, *offset).await?;
+    }
+
+    Ok(())
+}
+
+/// Record that the clock was paused at `time`
+pub async fn record_pause(db: impl SqliteExecutor<'_>, time: DateTime<Utc>) -> anyhow::Result<()> {
+    let paused_at = time.timestamp_millis();
+    sqlx::query!(
+        "INSERT INTO clock_pauses (paused_at) VALUES (?) ON CONFLICT DO NOTHING",
+        paused_at
+    )
+    .execute(db)
+    .await
+    .context("Failed to record pause")?;
+    Ok(())
+}
+
+/// Record that the clock was resumed at `time`, after which it had been paused for
+/// `total_time_paused` overall
+pub async fn record_unpause(
+    db: impl SqliteExecutor<'_> + Copy,
+    time: DateTime<Utc>,
+    total_time_paused: Duration,
+) -> anyhow::Result<()> {
+    let resumed_at = time.timestamp_millis();
+    sqlx::query!(
+        "UPDATE clock_pauses SET resumed_at = ? WHERE resumed_at IS NULL",
+        resumed_at
+    )
+    .execute(db)
+    .await
+    .context("Failed to record unpause")?;
+
+    let total_time_paused = total_time_paused.as_millis() as i64;
+    sqlx::query!(
+        "UPDATE clock SET total_time_paused = ? WHERE id = 0",
+        total_time_paused
+    )
+    .execute(db)
+    .await
+    .context("Failed to update total pause duration")?;
+    Ok(())
+}
+
+/// Record the total time that hosts have added to the time limit
+pub async fn record_adjustment(
+    db: impl SqliteExecutor<'_>,
+    time_adjustment: TimeDelta,
+) -> anyhow::Result<()> {
+    let time_adjustment = time_adjustment.num_milliseconds();
+    sqlx::query!(
+        "UPDATE clock SET time_adjustment = ? WHERE id = 0",
+        time_adjustment
+    )
+    .execute(db)
+    .await
+    .context("Failed to record time adjustment")?;
+    Ok(())
+}
+
+/// Record the time at which the scoreboard was frozen, or that it is no longer frozen
+pub async fn record_freeze(
+    db: impl SqliteExecutor<'_>,
+    frozen_at: Option<DateTime<Utc>>,
+) -> anyhow::Result<()> {
+    let frozen_at = frozen_at.map(|t| t.timestamp_millis());
+    sqlx::query!("UPDATE clock SET frozen_at = ? WHERE id = 0", frozen_at)
+        .execute(db)
+        .await
+        .context("Failed to record scoreboard freeze")?;
+    Ok(())
+}
+
+/// Record the time given to the team `id` on top of the time limit
+pub async fn set_team_offset(
+    db: impl SqliteExecutor<'_>,
+    id: &UserId,
+    offset: TimeDelta,
+) -> anyhow::Result<()> {
+    if offset.is_zero() {
+        sqlx::query!("DELETE FROM team_time_offsets WHERE user_id = ?", id)
+            .execute(db)
+            .await
+            .context("Failed to clear team time offset")?;
+        return Ok(());
+    }
+
+    let time_offset = offset.num_milliseconds();
+    sqlx::query!(
+        r#"
+        INSERT INTO team_time_offsets (user_id, time_offset) VALUES (?, ?)
+        ON CONFLICT (user_id) DO UPDATE SET time_offset = excluded.time_offset
+        "#,
+        id,
+        time_offset,
+    )
+    .execute(db)
+    .await
+    .context("Failed to record team time offset")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        repositories::users::Role,
+        testing::{mock_db, users_repositories},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn clock_round_trip() {
+        let db = mock_db().await;
+        assert!(get_clock(&db).await.unwrap().is_none());
+
+        // milliseconds are the finest resolution that is stored
+        let start = DateTime::from_timestamp_millis(Utc::now().timestamp_millis()).unwrap();
+        let mut clock = ClockInfo {
+            start_time: start,
+            pause_time: Some(start),
+            total_time_paused: Duration::ZERO,
+            time_adjustment: TimeDelta::zero(),
+            team_offsets: Default::default(),
+            frozen_at: None,
+        };
+        create_clock(&db, &clock).await.unwrap();
+        assert_eq!(get_clock(&db).await.unwrap().as_ref(), Some(&clock));
+
+        let resumed = start + TimeDelta::seconds(90);
+        clock.unpause_at(resumed);
+        record_unpause(&db, resumed, clock.total_time_paused)
+            .await
+            .unwrap();
+        assert_eq!(get_clock(&db).await.unwrap().as_ref(), Some(&clock));
+
+        let paused = start + TimeDelta::minutes(10);
+        clock.pause_at(paused);
+        record_pause(&db, paused).await.unwrap();
+        assert_eq!(get_clock(&db).await.unwrap().as_ref(), Some(&clock));
+
+        clock.adjust(TimeDelta::minutes(-10));
+        record_adjustment(&db, clock.time_adjustment).await.unwrap();
+        assert_eq!(get_clock(&db).await.unwrap().as_ref(), Some(&clock));
+
+        let team = users_repositories::dummy_user(&db, "team", "", Role::Competitor).await;
+        clock.set_team_offset(team.id, TimeDelta::minutes(20));
+        set_team_offset(&db, &team.id, TimeDelta::minutes(20))
+            .await
+            .unwrap();
+        assert_eq!(get_clock(&db).await.unwrap().as_ref(), Some(&clock));
+
+        clock.frozen_at = Some(start + TimeDelta::minutes(50));
+        record_freeze(&db, clock.frozen_at).await.unwrap();
+        assert_eq!(get_clock(&db).await.unwrap().as_ref(), Some(&clock));
+
+        clock.set_team_offset(team.id, TimeDelta::zero());
+        set_team_offset(&db, &team.id, TimeDelta::zero())
+            .await
+            .unwrap();
+        assert_eq!(get_clock(&db).await.unwrap().as_ref(), Some(&clock));
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::SqliteExecutor;
+
+/// Details of a competition, recorded when its database was created
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Competition {
+    /// Title of the packet
+    pub title: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Get the details of the competition, `None` if the database was created before they were
+/// recorded
+pub async fn get_competition(db: impl SqliteExecutor<'_>) -> anyhow::Result<Option<Competition>> {
+    let Some(row) = sqlx::query!("SELECT title, created_at FROM competition WHERE id = 0")
+        .fetch_optional(db)
+        .await
+        .context("Failed to get competition")?
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(Competition {
+        title: row.title,
+        created_at: DateTime::from_timestamp_millis(row.created_at)
+            .context("Stored creation time is out of range")?,
+    }))
+}
+
+/// Record the details of the competition, replacing any that were recorded before
+pub async fn set_competition(
+    db: impl SqliteExecutor<'_>,
+    competition: &Competition,
+) -> anyhow::Result<()> {
+    let created_at = competition.created_at.timestamp_millis();
+    sqlx::query!(
+        r#"
+        INSERT INTO competition (id, title, created_at)
+        VALUES (0, ?, ?)
+        ON CONFLICT (id) DO UPDATE SET
+            title = excluded.title,
+            created_at = excluded.created_at
+        "#,
+        competition.title,
+        created_at,
+    )
+    .execute(db)
+    .await
+    .context("Failed to save competition")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::SubsecRound;
+
+    use crate::testing::mock_db;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn save_competition() {
+        let db = mock_db().await;
+        assert_eq!(get_competition(&db).await.unwrap(), None);
+
+        let competition = Competition {
+            title: "Example Competition".into(),
+            created_at: Utc::now().trunc_subsecs(3),
+        };
+        set_competition(&db, &competition).await.unwrap();
+        assert_eq!(get_competition(&db).await.unwrap(), Some(competition));
+    }
+}
@@ -1,4 +1,7 @@
 pub mod announcements;
+pub mod clock;
+pub mod competition;
+pub mod problems;
 pub mod session;
 pub mod submissions;
 pub mod users;
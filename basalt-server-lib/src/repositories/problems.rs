@@ -0,0 +1,43 @@
+use anyhow::Context;
+use sqlx::SqliteExecutor;
+
+/// Get the indices of the problems which have been released
+pub async fn get_released_problems(db: impl SqliteExecutor<'_>) -> anyhow::Result<Vec<usize>> {
+    sqlx::query_scalar!("SELECT question_index FROM released_problems ORDER BY question_index")
+        .fetch_all(db)
+        .await
+        .context("Failed to get released problems")
+        .map(|indices| indices.into_iter().map(|i| i as usize).collect())
+}
+
+/// Record that the problem at `index` has been released.  Returns whether it had not been
+/// released before.
+pub async fn release_problem(db: impl SqliteExecutor<'_>, index: usize) -> anyhow::Result<bool> {
+    let index = index as i64;
+    sqlx::query!(
+        "INSERT INTO released_problems (question_index) VALUES (?) ON CONFLICT DO NOTHING",
+        index
+    )
+    .execute(db)
+    .await
+    .context("Failed to release problem")
+    .map(|r| r.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::mock_db;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn release_once() {
+        let db = mock_db().await;
+        assert!(get_released_problems(&db).await.unwrap().is_empty());
+
+        assert!(release_problem(&db, 3).await.unwrap());
+        assert!(!release_problem(&db, 3).await.unwrap());
+        assert!(release_problem(&db, 1).await.unwrap());
+        assert_eq!(get_released_problems(&db).await.unwrap(), vec![1, 3]);
+    }
+}
@@ -74,7 +74,7 @@ pub async fn get_user_from_session(
         role: Role,
     }
 
-    let session = sqlx::query_as!(SessionUser, "SELECT users.*, expires_at FROM users JOIN sessions ON users.id = sessions.user_id WHERE session_id = $1", session_id)
+    let session = sqlx::query_as!(SessionUser, "SELECT users.*, expires_at FROM users JOIN sessions ON users.id = sessions.user_id WHERE session_id = $1 AND users.id NOT IN (SELECT user_id FROM disabled_users)", session_id)
         .fetch_optional(sql)
         .await
         .map_err(|e| GetSessionError::QueryError(e.to_string()))?
@@ -118,3 +118,16 @@ pub async fn close_session(
 
     Ok(())
 }
+
+/// End every session of a user, such as once their password has changed.  Returns the number of
+/// sessions that were ended.
+pub async fn close_user_sessions(
+    db: impl SqliteExecutor<'_>,
+    user_id: &UserId,
+) -> Result<u64, CloseSessionError> {
+    let result = sqlx::query!("DELETE FROM sessions WHERE user_id = $1", user_id)
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected())
+}
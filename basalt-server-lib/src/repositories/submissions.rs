@@ -63,16 +63,61 @@ pub struct NewSubmissionHistory<'a> {
     pub code: &'a str,
     pub question_index: usize,
     pub language: &'a str,
-    pub compile_result: Option<&'a CompileResult>,
+    pub compile_result: Option<CompileOutput<'a>>,
     pub test_only: bool,
 }
 
+/// Output of compiling a submission
+#[derive(Clone, Debug)]
+pub struct CompileOutput<'a> {
+    pub state: CompileResultState,
+    pub stdout: Cow<'a, str>,
+    pub stderr: Cow<'a, str>,
+    pub exit_status: i32,
+}
+
+impl CompileOutput<'_> {
+    pub fn into_owned(self) -> CompileOutput<'static> {
+        CompileOutput {
+            state: self.state,
+            stdout: Cow::Owned(self.stdout.into_owned()),
+            stderr: Cow::Owned(self.stderr.into_owned()),
+            exit_status: self.exit_status,
+        }
+    }
+}
+
+impl<'a> From<&'a CompileResult> for CompileOutput<'a> {
+    fn from(value: &'a CompileResult) -> Self {
+        Self {
+            state: Some(value.state()).into(),
+            stdout: value.stdout().to_str_lossy(),
+            stderr: value.stderr().to_str_lossy(),
+            exit_status: value.exit_status(),
+        }
+    }
+}
+
 define_sqlx_enum! {
-    pub enum TestResultState(erudite::runner::TestResultState) {
-        Pass = erudite::runner::TestResultState::Pass,
-        RuntimeFail = erudite::runner::TestResultState::RuntimeFail,
-        TimedOut = erudite::runner::TestResultState::TimedOut,
-        IncorrectOutput = erudite::runner::TestResultState::IncorrectOutput
+    pub enum TestResultState {
+        Pass,
+        RuntimeFail,
+        TimedOut,
+        IncorrectOutput,
+        MemoryLimitExceeded,
+        OutputLimitExceeded,
+    }
+}
+
+// the test runner does not know about resource limits, see `server::limits`
+impl From<erudite::runner::TestResultState> for TestResultState {
+    fn from(value: erudite::runner::TestResultState) -> Self {
+        match value {
+            erudite::runner::TestResultState::Pass => Self::Pass,
+            erudite::runner::TestResultState::RuntimeFail => Self::RuntimeFail,
+            erudite::runner::TestResultState::TimedOut => Self::TimedOut,
+            erudite::runner::TestResultState::IncorrectOutput => Self::IncorrectOutput,
+        }
     }
 }
 
@@ -187,6 +232,114 @@ impl PartialSubmissionHistory {
     }
 }
 
+/// Get all submissions which were started but never finished, e.g. because the server stopped
+/// while they were being judged
+pub async fn get_unfinished_submissions(
+    db: impl Executor<'_, Database = Sqlite>,
+) -> anyhow::Result<Vec<SubmissionHistory>> {
+    sqlx::query_as!(
+        SubmissionHistory,
+        r#"
+            SELECT * FROM submission_history
+            WHERE state = ?
+            ORDER BY time
+            "#,
+        SubmissionState::Started,
+    )
+    .fetch_all(db)
+    .await
+    .context("Failed to get unfinished submissions")
+}
+
+/// Get the submissions which have been judged, in the order in which they were made.  Tests and
+/// submissions which are still being judged or were cancelled are excluded.  If `question_index`
+/// or `submitter` are set, only the submissions for that question or by that team are included.
+pub async fn get_judged_submissions(
+    db: impl SqliteExecutor<'_>,
+    question_index: Option<usize>,
+    submitter: Option<&UserId>,
+) -> anyhow::Result<Vec<SubmissionHistory>> {
+    let question_index = question_index.map(|i| i as i64);
+    sqlx::query_as!(
+        SubmissionHistory,
+        r#"
+            SELECT * FROM submission_history
+            WHERE test_only = FALSE
+                AND state IN (?1, ?2)
+                AND (?3 IS NULL OR question_index = ?3)
+                AND (?4 IS NULL OR submitter = ?4)
+            ORDER BY time, rowid
+            "#,
+        SubmissionState::Finished,
+        SubmissionState::Failed,
+        question_index,
+        submitter,
+    )
+    .fetch_all(db)
+    .await
+    .context("Failed to get judged submissions")
+}
+
+/// Values of the `compile_*` columns of a submission
+fn compile_columns(output: Option<CompileOutput<'_>>) -> (CompileResultState, String, String, i64) {
+    match output {
+        Some(output) => (
+            output.state,
+            output.stdout.into_owned(),
+            output.stderr.into_owned(),
+            output.exit_status as i64,
+        ),
+        None => (
+            CompileResultState::NoCompile,
+            String::new(),
+            String::new(),
+            0,
+        ),
+    }
+}
+
+/// Reset a submission so that it can be judged again, removing its test results and replacing its
+/// compile result.
+pub async fn reset_submission(
+    db: impl SqliteExecutor<'_> + Copy,
+    id: SubmissionId,
+    compile_result: Option<CompileOutput<'_>>,
+    state: SubmissionState,
+) -> anyhow::Result<SubmissionHistory> {
+    let (compile_state, compile_stdout, compile_stderr, compile_exit_status) =
+        compile_columns(compile_result);
+
+    sqlx::query!("DELETE FROM test_results WHERE submission = ?", id)
+        .execute(db)
+        .await
+        .context("Failed to delete test results")?;
+
+    sqlx::query_as!(SubmissionHistory, r#"
+            UPDATE submission_history
+                SET compile_result = ?,
+                compile_stdout = ?,
+                compile_stderr = ?,
+                compile_exit_status = ?,
+                state = ?,
+                score = 0.0,
+                success = FALSE,
+                time_taken = 0,
+                passed = 0,
+                failed = 0
+            WHERE id = ?
+            RETURNING id, submitter, time, code, question_index, language, compile_result, compile_stdout, compile_stderr, compile_exit_status, test_only, state, score, passed, failed, success, time_taken"#,
+        compile_state,
+        compile_stdout,
+        compile_stderr,
+        compile_exit_status,
+        state,
+        id,
+    )
+    .fetch_one(db)
+    .await
+    .context("Failed to reset submission")
+}
+
 pub async fn get_submission(
     db: impl Executor<'_, Database = Sqlite>,
     id: SubmissionId,
@@ -227,19 +380,8 @@ pub async fn create_failed_submission_history<'a>(
     new: NewSubmissionHistory<'a>,
 ) -> anyhow::Result<SubmissionHistory> {
     let question_index = new.question_index as i64;
-    let compile_result: CompileResultState = new.compile_result.map(|r| r.state()).into();
-    let compile_stdout = new
-        .compile_result
-        .map(|r| r.stdout().to_str_lossy())
-        .unwrap_or(Cow::Borrowed(""));
-    let compile_stderr = new
-        .compile_result
-        .map(|r| r.stderr().to_str_lossy())
-        .unwrap_or(Cow::Borrowed(""));
-    let compile_exit_status = new
-        .compile_result
-        .map(|r| r.exit_status())
-        .unwrap_or_default() as i64;
+    let (compile_result, compile_stdout, compile_stderr, compile_exit_status) =
+        compile_columns(new.compile_result);
 
     let hist = sqlx::query_as!(SubmissionHistory, r#"
             INSERT INTO submission_history (id, submitter, code, question_index, language, compile_result, compile_stdout, compile_stderr, compile_exit_status, test_only, state)
@@ -266,19 +408,8 @@ pub async fn create_submission_history<'a>(
     new: NewSubmissionHistory<'a>,
 ) -> anyhow::Result<PartialSubmissionHistory> {
     let question_index = new.question_index as i64;
-    let compile_result: CompileResultState = new.compile_result.map(|r| r.state()).into();
-    let compile_stdout = new
-        .compile_result
-        .map(|r| r.stdout().to_str_lossy())
-        .unwrap_or(Cow::Borrowed(""));
-    let compile_stderr = new
-        .compile_result
-        .map(|r| r.stderr().to_str_lossy())
-        .unwrap_or(Cow::Borrowed(""));
-    let compile_exit_status = new
-        .compile_result
-        .map(|r| r.exit_status())
-        .unwrap_or_default() as i64;
+    let (compile_result, compile_stdout, compile_stderr, compile_exit_status) =
+        compile_columns(new.compile_result);
 
     let hist = sqlx::query_as!(SubmissionHistory, r#"
             INSERT INTO submission_history (id, submitter, code, question_index, language, compile_result, compile_stdout, compile_stderr, compile_exit_status, test_only)
@@ -362,10 +493,44 @@ pub async fn count_previous_submissions<'a>(
             AND submitter = ?
             AND test_only = FALSE
             AND success = FALSE
+            AND NOT (state = ? AND compile_result IN (?, ?))
             AND time < CURRENT_TIMESTAMP
         "#,
         question_index,
         submitter,
+        SubmissionState::Failed,
+        CompileResultState::NoCompile,
+        CompileResultState::Success,
+    )
+    .fetch_one(db)
+    .await
+    .context("Failed to create submission test history")?;
+
+    Ok(attempts as _)
+}
+
+/// Like [`count_other_submissions`], but only counting submissions made before the submission
+/// `before`.  Used when judging a submission again, so that it is scored as it would have been
+/// originally.
+///
+/// Times are only stored to the second, so submissions made in the same second are ordered by
+/// when they were inserted.
+pub async fn count_other_submissions_before(
+    db: impl Executor<'_, Database = Sqlite>,
+    question_index: usize,
+    before: &SubmissionId,
+) -> anyhow::Result<u32> {
+    let question_index = question_index as i64;
+    let attempts = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(submitter) FROM submission_history
+        WHERE question_index = ?
+            AND test_only = FALSE
+            AND success = TRUE
+            AND (time, rowid) < (SELECT time, rowid FROM submission_history WHERE id = ?)
+        "#,
+        question_index,
+        before,
     )
     .fetch_one(db)
     .await
@@ -374,21 +539,87 @@ pub async fn count_previous_submissions<'a>(
     Ok(attempts as _)
 }
 
+/// Like [`count_previous_submissions`], but only counting submissions made before the submission
+/// `before`, in the same order as [`count_other_submissions_before`]
+pub async fn count_previous_submissions_before(
+    db: impl Executor<'_, Database = Sqlite>,
+    submitter: &UserId,
+    question_index: usize,
+    before: &SubmissionId,
+) -> anyhow::Result<u32> {
+    let question_index = question_index as i64;
+    let attempts = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(id) FROM submission_history
+        WHERE question_index = ?
+            AND submitter = ?
+            AND test_only = FALSE
+            AND success = FALSE
+            AND NOT (state = ? AND compile_result IN (?, ?))
+            AND (time, rowid) < (SELECT time, rowid FROM submission_history WHERE id = ?)
+        "#,
+        question_index,
+        submitter,
+        SubmissionState::Failed,
+        CompileResultState::NoCompile,
+        CompileResultState::Success,
+        before,
+    )
+    .fetch_one(db)
+    .await
+    .context("Failed to create submission test history")?;
+
+    Ok(attempts as _)
+}
+
+/// Get the total score of the latest submissions of the user, as in [`get_latest_submissions`]
 pub async fn get_user_score(db: impl SqliteExecutor<'_>, user_id: &UserId) -> anyhow::Result<f64> {
     sqlx::query_scalar!(
         r#"
             SELECT SUM(h.score)
             FROM submission_history h
-            JOIN (
-                SELECT question_index, MAX(time) AS latest
-                FROM submission_history
-                WHERE submitter = ? AND NOT test_only
-                GROUP BY question_index
-            ) t ON h.question_index = t.question_index AND h.time = t.latest
-            WHERE h.submitter = ? AND NOT h.test_only;
+            WHERE h.submitter = ? AND NOT h.test_only
+                AND NOT EXISTS (
+                    SELECT 1 FROM submission_history l
+                    WHERE l.submitter = h.submitter
+                        AND l.question_index = h.question_index
+                        AND NOT l.test_only
+                        AND (l.time, l.rowid) > (h.time, h.rowid)
+                );
         "#,
         user_id,
+    )
+    .fetch_one(db)
+    .await
+    .context("while querying the user's score")
+    .map(Option::unwrap_or_default)
+}
+
+/// Like [`get_user_score`], but only counting submissions made before `before` and those which
+/// have since been revealed.  Used to show scores as of the time that the scoreboard was frozen.
+pub async fn get_user_score_before(
+    db: impl SqliteExecutor<'_>,
+    user_id: &UserId,
+    before: OffsetDateTime,
+) -> anyhow::Result<f64> {
+    sqlx::query_scalar!(
+        r#"
+            SELECT SUM(h.score)
+            FROM submission_history h
+            WHERE h.submitter = ? AND NOT h.test_only
+                AND (h.time < datetime(?) OR h.id IN (SELECT submission_id FROM revealed_submissions))
+                AND NOT EXISTS (
+                    SELECT 1 FROM submission_history l
+                    WHERE l.submitter = h.submitter
+                        AND l.question_index = h.question_index
+                        AND NOT l.test_only
+                        AND (l.time < datetime(?) OR l.id IN (SELECT submission_id FROM revealed_submissions))
+                        AND (l.time, l.rowid) > (h.time, h.rowid)
+                );
+        "#,
         user_id,
+        before,
+        before,
     )
     .fetch_one(db)
     .await
@@ -396,6 +627,8 @@ pub async fn get_user_score(db: impl SqliteExecutor<'_>, user_id: &UserId) -> an
     .map(Option::unwrap_or_default)
 }
 
+/// Get the latest graded submission of the user for each question.  Times are only stored to the
+/// second, so of the submissions made in the same second, the one inserted last is the latest.
 pub async fn get_latest_submissions(
     db: impl SqliteExecutor<'_>,
     user_id: &UserId,
@@ -405,23 +638,187 @@ pub async fn get_latest_submissions(
         r#"
             SELECT h.*
             FROM submission_history h
-            JOIN (
-                SELECT question_index, MAX(time) AS latest
-                FROM submission_history
-                WHERE submitter = ?
-                    AND test_only = FALSE
-                GROUP BY question_index
-            ) t ON h.question_index = t.question_index AND h.time = t.latest
-            WHERE h.submitter = ?;
+            WHERE h.submitter = ?
+                AND h.test_only = FALSE
+                AND NOT EXISTS (
+                    SELECT 1 FROM submission_history l
+                    WHERE l.submitter = h.submitter
+                        AND l.question_index = h.question_index
+                        AND l.test_only = FALSE
+                        AND (l.time, l.rowid) > (h.time, h.rowid)
+                );
         "#,
         user_id,
+    )
+    .fetch_all(db)
+    .await
+    .context("while querying the user's question states")
+}
+
+/// Like [`get_latest_submissions`], but only considering submissions made before `before` and
+/// those which have since been revealed
+pub async fn get_latest_submissions_before(
+    db: impl SqliteExecutor<'_>,
+    user_id: &UserId,
+    before: OffsetDateTime,
+) -> anyhow::Result<Vec<SubmissionHistory>> {
+    sqlx::query_as!(
+        SubmissionHistory,
+        r#"
+            SELECT h.*
+            FROM submission_history h
+            WHERE h.submitter = ?
+                AND h.test_only = FALSE
+                AND (h.time < datetime(?) OR h.id IN (SELECT submission_id FROM revealed_submissions))
+                AND NOT EXISTS (
+                    SELECT 1 FROM submission_history l
+                    WHERE l.submitter = h.submitter
+                        AND l.question_index = h.question_index
+                        AND l.test_only = FALSE
+                        AND (l.time < datetime(?) OR l.id IN (SELECT submission_id FROM revealed_submissions))
+                        AND (l.time, l.rowid) > (h.time, h.rowid)
+                );
+        "#,
         user_id,
+        before,
+        before,
     )
     .fetch_all(db)
     .await
     .context("while querying the user's question states")
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Solve {
+    pub question_index: i64,
+    /// Time of the first submission that passed all tests
+    pub time: OffsetDateTime,
+}
+
+/// Get the time at which the user first solved each question
+pub async fn get_first_solves(
+    db: impl SqliteExecutor<'_>,
+    user_id: &UserId,
+) -> anyhow::Result<Vec<Solve>> {
+    sqlx::query_as!(
+        Solve,
+        r#"
+            SELECT question_index, MIN(time) as "time!: OffsetDateTime"
+            FROM submission_history
+            WHERE submitter = ?
+                AND test_only = FALSE
+                AND success = TRUE
+            GROUP BY question_index
+            ORDER BY question_index
+        "#,
+        user_id
+    )
+    .fetch_all(db)
+    .await
+    .context("while querying the user's solves")
+}
+
+/// Like [`get_first_solves`], but only considering submissions made before `before` and those
+/// which have since been revealed
+pub async fn get_first_solves_before(
+    db: impl SqliteExecutor<'_>,
+    user_id: &UserId,
+    before: OffsetDateTime,
+) -> anyhow::Result<Vec<Solve>> {
+    sqlx::query_as!(
+        Solve,
+        r#"
+            SELECT question_index, MIN(time) as "time!: OffsetDateTime"
+            FROM submission_history
+            WHERE submitter = ?
+                AND test_only = FALSE
+                AND success = TRUE
+                AND (time < datetime(?) OR id IN (SELECT submission_id FROM revealed_submissions))
+            GROUP BY question_index
+            ORDER BY question_index
+        "#,
+        user_id,
+        before,
+    )
+    .fetch_all(db)
+    .await
+    .context("while querying the user's solves")
+}
+
+/// Get the submissions made since `since` which have been judged but not revealed, in the order
+/// in which they were made
+pub async fn get_hidden_submissions(
+    db: impl SqliteExecutor<'_>,
+    since: OffsetDateTime,
+) -> anyhow::Result<Vec<SubmissionHistory>> {
+    sqlx::query_as!(
+        SubmissionHistory,
+        r#"
+            SELECT *
+            FROM submission_history
+            WHERE test_only = FALSE
+                AND state IN (?, ?)
+                AND time >= datetime(?)
+                AND id NOT IN (SELECT submission_id FROM revealed_submissions)
+            ORDER BY time
+        "#,
+        SubmissionState::Finished,
+        SubmissionState::Failed,
+        since,
+    )
+    .fetch_all(db)
+    .await
+    .context("while querying hidden submissions")
+}
+
+/// Count the submissions made since `since` which are still being judged
+pub async fn count_judging_submissions(
+    db: impl SqliteExecutor<'_>,
+    since: OffsetDateTime,
+) -> anyhow::Result<i64> {
+    sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*)
+            FROM submission_history
+            WHERE test_only = FALSE
+                AND state = ?
+                AND time >= datetime(?)
+        "#,
+        SubmissionState::Started,
+        since,
+    )
+    .fetch_one(db)
+    .await
+    .context("while counting submissions being judged")
+}
+
+/// Reveal a submission that was made while the scoreboard was frozen
+pub async fn reveal_submission(
+    db: impl SqliteExecutor<'_>,
+    id: &SubmissionId,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "INSERT INTO revealed_submissions (submission_id) VALUES (?) ON CONFLICT DO NOTHING",
+        id
+    )
+    .execute(db)
+    .await
+    .context("while revealing submission")?;
+    Ok(())
+}
+
+/// Record that a user has solved every problem.  Returns `false` if this was already recorded.
+pub async fn record_completion(db: impl SqliteExecutor<'_>, user: &UserId) -> anyhow::Result<bool> {
+    let result = sqlx::query!(
+        "INSERT INTO completions (user_id) VALUES (?) ON CONFLICT DO NOTHING",
+        user
+    )
+    .execute(db)
+    .await
+    .context("while recording completion")?;
+    Ok(result.rows_affected() > 0)
+}
+
 #[derive(Serialize, Deserialize, sqlx::FromRow)]
 pub struct Attempt {
     pub question_index: i64,
@@ -438,9 +835,13 @@ pub async fn get_attempts(
             SELECT question_index, count(id) as attempts
             FROM submission_history
             WHERE submitter = ?
+                AND NOT (state = ? AND compile_result IN (?, ?))
             GROUP BY question_index;
         "#,
-        user_id
+        user_id,
+        SubmissionState::Failed,
+        CompileResultState::NoCompile,
+        CompileResultState::Success,
     )
     .fetch_all(db)
     .await
@@ -512,13 +913,129 @@ pub async fn get_submissions(
     .context("getting user submissions")
 }
 
+pub async fn get_all_submissions(
+    db: impl SqliteExecutor<'_>,
+) -> anyhow::Result<Vec<SubmissionHistory>> {
+    sqlx::query_as!(
+        SubmissionHistory,
+        r#"
+        SELECT * FROM submission_history
+        ORDER BY time ASC;
+        "#,
+    )
+    .fetch_all(db)
+    .await
+    .context("getting all submissions")
+}
+
+pub async fn get_all_test_results(db: impl SqliteExecutor<'_>) -> anyhow::Result<Vec<TestResults>> {
+    sqlx::query_as!(
+        TestResults,
+        r#"
+        SELECT * FROM test_results
+        ORDER BY submission, test_index;
+        "#,
+    )
+    .fetch_all(db)
+    .await
+    .context("getting all test results")
+}
+
+/// Delete all submissions made by a user, along with their test results.  Returns the number of
+/// submissions deleted.
+pub async fn delete_user_submissions(
+    conn: &mut sqlx::SqliteConnection,
+    user_id: &UserId,
+) -> anyhow::Result<u64> {
+    sqlx::query!(
+        r#"
+        DELETE FROM test_results
+        WHERE submission IN (SELECT id FROM submission_history WHERE submitter = ?)
+        "#,
+        user_id,
+    )
+    .execute(&mut *conn)
+    .await
+    .context("deleting user test results")?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM revealed_submissions
+        WHERE submission_id IN (SELECT id FROM submission_history WHERE submitter = ?)
+        "#,
+        user_id,
+    )
+    .execute(&mut *conn)
+    .await
+    .context("deleting user revealed submissions")?;
+
+    sqlx::query!(
+        "DELETE FROM submission_history WHERE submitter = ?",
+        user_id
+    )
+    .execute(&mut *conn)
+    .await
+    .context("deleting user submissions")
+    .map(|r| r.rows_affected())
+}
+
+/// Overall number of submissions that have been stored in a competition
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct SubmissionCounts {
+    /// Submissions that were graded against all test cases
+    pub submissions: i64,
+    /// Graded submissions that passed all test cases
+    pub accepted: i64,
+    /// Runs against only the visible test cases
+    pub tests: i64,
+}
+
+pub async fn count_all_submissions(
+    db: impl SqliteExecutor<'_>,
+) -> anyhow::Result<SubmissionCounts> {
+    #[derive(sqlx::FromRow)]
+    struct Group {
+        test_only: bool,
+        success: bool,
+        count: i64,
+    }
+
+    let groups = sqlx::query_as!(
+        Group,
+        r#"
+            SELECT test_only, success, count(id) as count
+            FROM submission_history
+            GROUP BY test_only, success
+        "#
+    )
+    .fetch_all(db)
+    .await
+    .context("while counting submissions")?;
+
+    Ok(groups
+        .into_iter()
+        .fold(SubmissionCounts::default(), |mut counts, group| {
+            if group.test_only {
+                counts.tests += group.count;
+            } else {
+                counts.submissions += group.count;
+                if group.success {
+                    counts.accepted += group.count;
+                }
+            }
+            counts
+        }))
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
 
     use crate::{
         repositories::users::Role,
-        testing::{mock_db, users_repositories::dummy_user},
+        testing::{
+            mock_db, submissions_repositories::dummy_submission, users_repositories::dummy_user,
+        },
     };
 
     use super::*;
@@ -677,12 +1194,206 @@ mod tests {
             .unwrap();
         }
 
+        // interrupted submissions don't count as attempts
+        create_submission_history(
+            &sql,
+            NewSubmissionHistory {
+                id: SubmissionId::new(),
+                test_only: false,
+                submitter: user.id,
+                code: "",
+                question_index: 1,
+                language: "java",
+                compile_result: None,
+            },
+        )
+        .await
+        .unwrap()
+        .fail(&sql)
+        .await
+        .unwrap();
+
         tokio::time::sleep(Duration::from_secs(1)).await;
 
         let n = count_previous_submissions(&sql, &user.id, 1).await.unwrap();
         assert_eq!(n, 5);
     }
 
+    #[tokio::test]
+    async fn unfinished_submissions() {
+        let sql = mock_db().await;
+
+        let user = dummy_user(&sql, "dummy_user", "foobar", Role::Competitor).await;
+        let finished = dummy_submission(&sql, &user, 1., 0).await;
+        let unfinished = create_submission_history(
+            &sql,
+            NewSubmissionHistory {
+                id: SubmissionId::new(),
+                test_only: false,
+                submitter: user.id,
+                code: "",
+                question_index: 0,
+                language: "java",
+                compile_result: None,
+            },
+        )
+        .await
+        .unwrap();
+        create_test_results(
+            &sql,
+            &unfinished.id,
+            0,
+            NewTestResults {
+                result: TestResultState::Pass,
+                stdout: "".into(),
+                stderr: "".into(),
+                exit_status: 0,
+                time_taken: Duration::from_secs(1).into(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let found = get_unfinished_submissions(&sql).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, unfinished.id);
+
+        let reset = reset_submission(&sql, unfinished.id, None, SubmissionState::Failed)
+            .await
+            .unwrap();
+        assert_eq!(reset.state, SubmissionState::Failed);
+        assert!(get_test_results(&sql, unfinished.id)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(get_unfinished_submissions(&sql).await.unwrap().is_empty());
+
+        let n = count_previous_submissions_before(&sql, &user.id, 0, &finished.id)
+            .await
+            .unwrap();
+        assert_eq!(n, 0);
+    }
+
+    #[tokio::test]
+    async fn submissions_in_the_same_second() {
+        let sql = mock_db().await;
+
+        let user = dummy_user(&sql, "dummy_user", "foobar", Role::Competitor).await;
+        let other = dummy_user(&sql, "other_user", "foobar", Role::Competitor).await;
+        dummy_submission(&sql, &other, 1., 0).await;
+        create_submission_history(
+            &sql,
+            NewSubmissionHistory {
+                id: SubmissionId::new(),
+                test_only: false,
+                submitter: user.id,
+                code: "",
+                question_index: 0,
+                language: "java",
+                compile_result: None,
+            },
+        )
+        .await
+        .unwrap()
+        .finish(&sql, 0.5, false, 0, 1, Duration::from_secs(1))
+        .await
+        .unwrap();
+        let rejudged = dummy_submission(&sql, &user, 1., 0).await;
+        dummy_submission(&sql, &other, 1., 0).await;
+
+        // times are only stored to the second
+        sqlx::query(
+            "UPDATE submission_history SET time = (SELECT time FROM submission_history WHERE id = ?)",
+        )
+        .bind(rejudged.id)
+        .execute(&sql)
+        .await
+        .unwrap();
+
+        let n = count_other_submissions_before(&sql, 0, &rejudged.id)
+            .await
+            .unwrap();
+        assert_eq!(n, 1);
+        let n = count_previous_submissions_before(&sql, &user.id, 0, &rejudged.id)
+            .await
+            .unwrap();
+        assert_eq!(n, 1);
+
+        let latest = get_latest_submissions(&sql, &user.id).await.unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].id, rejudged.id);
+        assert_eq!(get_user_score(&sql, &user.id).await.unwrap(), 1.);
+    }
+
+    #[tokio::test]
+    async fn judged_submissions() {
+        let sql = mock_db().await;
+
+        let first = dummy_user(&sql, "first", "foobar", Role::Competitor).await;
+        let second = dummy_user(&sql, "second", "foobar", Role::Competitor).await;
+        let a = dummy_submission(&sql, &first, 1., 0).await;
+        let b = dummy_submission(&sql, &second, 1., 0).await;
+        let c = dummy_submission(&sql, &first, 1., 1).await;
+        // still being judged
+        create_submission_history(
+            &sql,
+            NewSubmissionHistory {
+                id: SubmissionId::new(),
+                test_only: false,
+                submitter: first.id,
+                code: "",
+                question_index: 0,
+                language: "java",
+                compile_result: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        let ids = |submissions: Vec<SubmissionHistory>| {
+            submissions.into_iter().map(|s| s.id).collect::<Vec<_>>()
+        };
+        let all = get_judged_submissions(&sql, None, None).await.unwrap();
+        assert_eq!(ids(all), vec![a.id, b.id, c.id]);
+        let question = get_judged_submissions(&sql, Some(0), None).await.unwrap();
+        assert_eq!(ids(question), vec![a.id, b.id]);
+        let team = get_judged_submissions(&sql, None, Some(&first.id))
+            .await
+            .unwrap();
+        assert_eq!(ids(team), vec![a.id, c.id]);
+    }
+
+    #[tokio::test]
+    async fn all_submission_counts() {
+        let sql = mock_db().await;
+
+        let user = dummy_user(&sql, "dummy_user", "foobar", Role::Competitor).await;
+        for (test_only, success) in [(false, true), (false, false), (true, true), (true, false)] {
+            create_submission_history(
+                &sql,
+                NewSubmissionHistory {
+                    id: SubmissionId::new(),
+                    test_only,
+                    submitter: user.id,
+                    code: "",
+                    question_index: 0,
+                    language: "java",
+                    compile_result: None,
+                },
+            )
+            .await
+            .unwrap()
+            .finish(&sql, 0.0, success, 1, 0, Duration::from_secs(1))
+            .await
+            .unwrap();
+        }
+
+        let counts = count_all_submissions(&sql).await.unwrap();
+        assert_eq!(counts.submissions, 2);
+        assert_eq!(counts.accepted, 1);
+        assert_eq!(counts.tests, 2);
+    }
+
     #[tokio::test]
     async fn user_score() {
         let sql = mock_db().await;
@@ -6,7 +6,7 @@ use rand::rngs::OsRng;
 use redact::Secret;
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
-use sqlx::SqliteExecutor;
+use sqlx::{SqliteConnection, SqliteExecutor};
 use utoipa::ToSchema;
 
 use crate::{define_id_type, define_sqlx_enum};
@@ -56,6 +56,27 @@ pub async fn get_user_by_id(
         })
 }
 
+pub async fn get_user_by_username(
+    db: impl SqliteExecutor<'_>,
+    username: impl AsRef<str>,
+) -> Result<User, GetUserError> {
+    let username = username.as_ref();
+    sqlx::query_as!(User, "SELECT * from users WHERE username = $1", username)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| GetUserError::QueryError(e.to_string()))?
+        .ok_or(GetUserError::UserNotFound {
+            property: "username",
+            value: username.to_string(),
+        })
+}
+
+pub async fn get_all_users(db: impl SqliteExecutor<'_>) -> Result<Vec<User>, sqlx::Error> {
+    sqlx::query_as!(User, "SELECT * from users ORDER BY role DESC, username ASC")
+        .fetch_all(db)
+        .await
+}
+
 // I so desperately want this to return a stream, but sqlx makes that a total pain...
 pub async fn get_users_with_role(
     db: impl SqliteExecutor<'_>,
@@ -66,6 +87,23 @@ pub async fn get_users_with_role(
         .await
 }
 
+#[derive(Debug, Clone, Copy, FromRow)]
+pub struct RoleCount {
+    pub role: Role,
+    pub count: i64,
+}
+
+pub async fn count_users_by_role(
+    db: impl SqliteExecutor<'_>,
+) -> Result<Vec<RoleCount>, sqlx::Error> {
+    sqlx::query_as!(
+        RoleCount,
+        "SELECT role, COUNT(id) as count FROM users GROUP BY role"
+    )
+    .fetch_all(db)
+    .await
+}
+
 #[derive(Debug, FromRow, Deserialize)]
 pub struct UserLogin {
     pub username: String,
@@ -78,7 +116,7 @@ pub async fn login_user(
 ) -> Result<User, GetUserError> {
     let user = sqlx::query_as!(
         User,
-        "SELECT * from users WHERE username = $1",
+        "SELECT * from users WHERE username = $1 AND id NOT IN (SELECT user_id FROM disabled_users)",
         login.username,
     )
     .fetch_optional(db)
@@ -89,13 +127,7 @@ pub async fn login_user(
         value: login.username.to_string(),
     })?;
 
-    // user.password
-    let password_hash = PasswordHash::new(user.password_hash.expose_secret()).unwrap();
-
-    if Argon2::default()
-        .verify_password(login.password.expose_secret().as_bytes(), &password_hash)
-        .is_ok()
-    {
+    if verify_password(&user, login.password.expose_secret()) {
         Ok(user)
     } else {
         Err(GetUserError::UserNotFound {
@@ -122,6 +154,25 @@ impl Display for CreateUserError {
 
 impl std::error::Error for CreateUserError {}
 
+/// Hash a password using Argon2, for storage in [`User::password_hash`]
+pub fn hash_password(password: impl AsRef<[u8]>) -> Secret<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_ref(), &salt)
+        .expect("Failed to hash password")
+        .to_string()
+        .into()
+}
+
+/// Check whether `password` matches the stored password hash of `user`
+pub fn verify_password(user: &User, password: impl AsRef<[u8]>) -> bool {
+    let password_hash = PasswordHash::new(user.password_hash.expose_secret()).unwrap();
+
+    Argon2::default()
+        .verify_password(password.as_ref(), &password_hash)
+        .is_ok()
+}
+
 /// Creates a user and inserts into database.
 ///
 /// Uses Argon2 to hash the password
@@ -132,14 +183,10 @@ pub async fn create_user(
     password: impl AsRef<str>,
     role: Role,
 ) -> Result<User, CreateUserError> {
-    let salt = SaltString::generate(&mut OsRng);
     let id = UserId::new();
     let username: &str = username.as_ref();
-    let password: &str = password.as_ref();
-    let password_hash = Argon2::default()
-        .hash_password(password.as_ref(), &salt)
-        .expect("Failed to hash password")
-        .to_string();
+    let password_hash = hash_password(password.as_ref());
+    let password_hash = password_hash.expose_secret();
     let role_int: i32 = role.into();
     sqlx::query_as!(User,
         "INSERT INTO users (id, username, display_name, password_hash, role) VALUES (?, ?, ?, ?, ?) RETURNING id, username, display_name, password_hash, role",
@@ -204,6 +251,86 @@ pub async fn update_user(
     })
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum DeleteUserError {
+    #[error("User has submissions or announcements which must be removed first")]
+    HasHistory,
+    #[error("A database error occurred: {0}")]
+    QueryError(#[from] sqlx::Error),
+}
+
+/// Delete a user and all of their sessions.
+///
+/// Fails with [`DeleteUserError::HasHistory`] if the user still has submissions or announcements
+/// referencing them.
+pub async fn delete_user(
+    conn: &mut SqliteConnection,
+    id: &UserId,
+) -> Result<Option<User>, DeleteUserError> {
+    sqlx::query!("DELETE FROM sessions WHERE user_id = ?", id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!("DELETE FROM disabled_users WHERE user_id = ?", id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!("DELETE FROM team_time_offsets WHERE user_id = ?", id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!("DELETE FROM completions WHERE user_id = ?", id)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query_as!(
+        User,
+        "DELETE FROM users WHERE id = ? RETURNING id, username, display_name, password_hash, role",
+        id
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref dbe) if dbe.is_foreign_key_violation() => {
+            DeleteUserError::HasHistory
+        }
+        _ => DeleteUserError::QueryError(e),
+    })
+}
+
+/// Prevent a user from logging in, ending all of their current sessions
+pub async fn disable_user(conn: &mut SqliteConnection, id: &UserId) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM sessions WHERE user_id = ?", id)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query!(
+        "INSERT INTO disabled_users (user_id) VALUES (?) ON CONFLICT DO NOTHING",
+        id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Allow a previously disabled user to log in again.  Returns whether the user was disabled.
+pub async fn enable_user(db: impl SqliteExecutor<'_>, id: &UserId) -> Result<bool, sqlx::Error> {
+    sqlx::query!("DELETE FROM disabled_users WHERE user_id = ?", id)
+        .execute(db)
+        .await
+        .map(|r| r.rows_affected() > 0)
+}
+
+pub async fn is_user_disabled(
+    db: impl SqliteExecutor<'_>,
+    id: &UserId,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM disabled_users WHERE user_id = ?) as "disabled: bool""#,
+        id
+    )
+    .fetch_one(db)
+    .await
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum QuestionState {
@@ -243,6 +370,80 @@ mod tests {
         assert_eq!(user.username, dummy_user.username);
     }
 
+    #[tokio::test]
+    async fn delete_existing_user() {
+        let sql = mock_db().await;
+        let user = create_user(&sql, "foo", None, "password", Role::Competitor)
+            .await
+            .unwrap();
+
+        let mut conn = sql.acquire().await.unwrap();
+        let deleted = delete_user(&mut conn, &user.id).await.unwrap().unwrap();
+        assert_eq!(deleted.id, user.id);
+        assert!(get_user_by_id(&mut *conn, &user.id).await.is_err());
+        assert!(delete_user(&mut conn, &user.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn delete_user_with_history() {
+        let sql = mock_db().await;
+        let user = create_user(&sql, "foo", None, "password", Role::Competitor)
+            .await
+            .unwrap();
+        crate::testing::submissions_repositories::dummy_submission(&sql, &user, 1., 0).await;
+
+        let mut conn = sql.acquire().await.unwrap();
+        let err = delete_user(&mut conn, &user.id).await.unwrap_err();
+        assert!(matches!(err, DeleteUserError::HasHistory));
+        assert!(get_user_by_id(&sql, &user.id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn disabled_user_cannot_login() {
+        let sql = mock_db().await;
+        let user = create_user(&sql, "foo", None, "password", Role::Competitor)
+            .await
+            .unwrap();
+        let login = UserLogin {
+            username: "foo".into(),
+            password: "password".to_string().into(),
+        };
+        assert!(login_user(&sql, &login).await.is_ok());
+
+        let mut conn = sql.acquire().await.unwrap();
+        disable_user(&mut conn, &user.id).await.unwrap();
+        assert!(is_user_disabled(&mut *conn, &user.id).await.unwrap());
+        assert!(login_user(&mut *conn, &login).await.is_err());
+
+        assert!(enable_user(&mut *conn, &user.id).await.unwrap());
+        assert!(!enable_user(&mut *conn, &user.id).await.unwrap());
+        assert!(login_user(&mut *conn, &login).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn count_by_role() {
+        let sql = mock_db().await;
+        for name in ["team1", "team2", "team3"] {
+            create_user(&sql, name, None, "password", Role::Competitor)
+                .await
+                .unwrap();
+        }
+        create_user(&sql, "host", None, "password", Role::Host)
+            .await
+            .unwrap();
+
+        let counts = count_users_by_role(&sql).await.unwrap();
+        let count = |role| {
+            counts
+                .iter()
+                .find(|c| c.role == role)
+                .map(|c| c.count)
+                .unwrap_or_default()
+        };
+        assert_eq!(count(Role::Competitor), 3);
+        assert_eq!(count(Role::Host), 1);
+    }
+
     #[tokio::test]
     async fn get_correct_user() {
         let sql = mock_db().await;
@@ -0,0 +1,197 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use leucite::CommandExt;
+use serde::Deserialize;
+
+use crate::server::sandbox::{system_rules, WorkDir};
+
+/// How the output of a solution is compared with the expected output of a test
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Checker {
+    /// Output must match exactly, after trimming if `trim_output` is set in the packet
+    #[default]
+    Exact,
+    /// Output must contain the same whitespace-separated tokens, regardless of how they are
+    /// separated
+    Whitespace,
+    /// Like `whitespace`, but tokens are also compared ignoring case
+    CaseInsensitive,
+    /// Like `whitespace`, but tokens which are numbers only need to be within `tolerance` of the
+    /// expected number, either absolutely or relative to its size
+    Float { tolerance: f64 },
+    /// Output must contain the same lines, in any order.  Blank lines and surrounding whitespace
+    /// are ignored.
+    UnorderedLines,
+    /// Run a program which decides whether the output is correct.  The command is run with
+    /// `bash -c` from the directory containing the settings file, in a sandbox like that of
+    /// solutions, and is passed the paths of files containing the input, the expected output and
+    /// the actual output as its arguments.  The output is accepted if the program exits
+    /// successfully.
+    Program {
+        command: String,
+        /// Directory from which the command is run, set once the settings have been read.  The
+        /// current directory is used if it is not set.
+        #[serde(skip)]
+        dir: Option<PathBuf>,
+    },
+}
+
+impl Checker {
+    /// Whether this checker only accepts output that matches exactly, in which case the verdict
+    /// of the test runner stands
+    pub fn is_exact(&self) -> bool {
+        matches!(self, Checker::Exact)
+    }
+
+    /// Decide whether `actual` is a correct answer to a test with `input` and `expected` output.
+    /// `timeout` limits how long a checker program may run.
+    pub async fn accepts(
+        &self,
+        input: &str,
+        expected: &str,
+        actual: &str,
+        timeout: Duration,
+    ) -> anyhow::Result<bool> {
+        Ok(match self {
+            Checker::Exact => expected == actual,
+            Checker::Whitespace => tokens_match(expected, actual, |e, a| e == a),
+            Checker::CaseInsensitive => tokens_match(expected, actual, |e, a| {
+                e.to_lowercase() == a.to_lowercase()
+            }),
+            Checker::Float { tolerance } => tokens_match(expected, actual, |e, a| {
+                match (e.parse::<f64>(), a.parse::<f64>()) {
+                    (Ok(e), Ok(a)) if e.is_finite() && a.is_finite() => {
+                        (e - a).abs() <= tolerance * e.abs().max(1.)
+                    }
+                    _ => e == a,
+                }
+            }),
+            Checker::UnorderedLines => {
+                let lines = |s: &str| {
+                    let mut lines: Vec<_> =
+                        s.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+                    lines.sort_unstable();
+                    lines
+                };
+                lines(expected) == lines(actual)
+            }
+            Checker::Program { command, dir } => {
+                run_program(command, dir.as_deref(), input, expected, actual, timeout).await?
+            }
+        })
+    }
+}
+
+fn tokens_match(expected: &str, actual: &str, eq: impl Fn(&str, &str) -> bool) -> bool {
+    let mut expected = expected.split_whitespace();
+    let mut actual = actual.split_whitespace();
+    loop {
+        match (expected.next(), actual.next()) {
+            (None, None) => return true,
+            (Some(e), Some(a)) if eq(e, a) => {}
+            _ => return false,
+        }
+    }
+}
+
+async fn run_program(
+    command: &str,
+    dir: Option<&Path>,
+    input: &str,
+    expected: &str,
+    actual: &str,
+    timeout: Duration,
+) -> anyhow::Result<bool> {
+    let files_dir = WorkDir::new("checker").await?;
+    let mut files: Vec<PathBuf> = Vec::with_capacity(3);
+    for (file, content) in [("input", input), ("expected", expected), ("actual", actual)] {
+        let path = files_dir.path().join(file);
+        tokio::fs::write(&path, content)
+            .await
+            .with_context(|| format!("Writing {} for checker", file))?;
+        files.push(path);
+    }
+
+    // the checker is run from the directory containing the settings, so that it can refer to
+    // files next to them
+    let cwd = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => std::env::current_dir().context("Getting current directory")?,
+    };
+    let rules = system_rules()
+        .add_read_only(&cwd)
+        .add_read_write(files_dir.path());
+    let child = tokio::process::Command::new("bash")
+        .arg("-c")
+        .arg(command)
+        .arg("checker")
+        .args(&files)
+        .current_dir(&cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .restrict(Arc::new(rules))
+        .spawn()
+        .context("Spawning checker")?;
+
+    let status = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .context("Checker timed out")?
+        .context("Waiting for checker")?
+        .status;
+    Ok(status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    async fn accepts(checker: &Checker, expected: &str, actual: &str) -> bool {
+        checker
+            .accepts("", expected, actual, TIMEOUT)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn lenient_comparison() {
+        let whitespace = Checker::Whitespace;
+        assert!(accepts(&whitespace, "1 2\n3\n", "1  2 3").await);
+        assert!(!accepts(&whitespace, "1 2 3", "1 2").await);
+        assert!(!accepts(&whitespace, "Yes", "yes").await);
+        assert!(accepts(&Checker::CaseInsensitive, "Yes\n", "  yES").await);
+
+        let float = Checker::Float { tolerance: 1e-6 };
+        assert!(accepts(&float, "3.1415926 done", "3.14159265 done").await);
+        assert!(accepts(&float, "1000000", "1000000.5").await);
+        assert!(!accepts(&float, "3.14", "3.15").await);
+        assert!(!accepts(&float, "NaN", "nan").await);
+
+        let unordered = Checker::UnorderedLines;
+        assert!(accepts(&unordered, "a\nb\nc\n", "c\n a\n\nb").await);
+        assert!(!accepts(&unordered, "a\nb\nb", "a\na\nb").await);
+    }
+
+    #[test]
+    fn parse() {
+        #[derive(Deserialize)]
+        struct Problem {
+            checker: Checker,
+        }
+        let problem: Problem =
+            toml::from_str(r#"checker = { kind = "float", tolerance = 1e-4 }"#).unwrap();
+        assert_eq!(problem.checker, Checker::Float { tolerance: 1e-4 });
+        let problem: Problem = toml::from_str(r#"checker = { kind = "unordered-lines" }"#).unwrap();
+        assert_eq!(problem.checker, Checker::UnorderedLines);
+    }
+}
@@ -1,84 +1,263 @@
-use std::time::{Duration, Instant};
+use std::{collections::HashMap, time::Duration};
 
 use anyhow::Context;
+use chrono::{DateTime, TimeDelta, Utc};
+use time::OffsetDateTime;
+
+use crate::repositories::users::UserId;
 
 /// Tracks all information related to the in-game clock
+///
+/// Times are stored as wall-clock times so that the clock can be persisted and restored after a
+/// restart, see [`crate::repositories::clock`].
+#[derive(Debug, Clone, PartialEq)]
 pub struct ClockInfo {
     /// Time when the competition began
-    pub start_time: Instant,
+    pub start_time: DateTime<Utc>,
     /// Time that the competition has been paused.
     /// One can infer that the competition is paused if this value is `Some`
-    pub pause_time: Option<Instant>,
+    pub pause_time: Option<DateTime<Utc>>,
     /// Total duration the competition has been paused.
     pub total_time_paused: Duration,
+    /// Time that hosts have added to the time limit, negative if time has been removed
+    pub time_adjustment: TimeDelta,
+    /// Time given to individual teams on top of the time limit, such as for accessibility
+    /// accommodations or teams which started late
+    pub team_offsets: HashMap<UserId, TimeDelta>,
+    /// Time at which the scoreboard was frozen, if it is frozen.  Scores are shown as of this time
+    /// to everybody except hosts.
+    pub frozen_at: Option<DateTime<Utc>>,
 }
 
 impl Default for ClockInfo {
     fn default() -> Self {
+        let now = Utc::now();
         Self {
-            start_time: Instant::now(),
-            pause_time: Some(Instant::now()),
+            start_time: now,
+            pause_time: Some(now),
             total_time_paused: Default::default(),
+            time_adjustment: Default::default(),
+            team_offsets: Default::default(),
+            frozen_at: None,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct CurrentTime {
     pub paused: bool,
     pub duration: Duration,
+    pub time_adjustment: TimeDelta,
 }
 
 impl ClockInfo {
     pub fn pause(&mut self) -> bool {
+        self.pause_at(Utc::now())
+    }
+
+    /// Pause the clock as of `time`.  Returns whether the clock was running.
+    pub fn pause_at(&mut self, time: DateTime<Utc>) -> bool {
         let affected = self.pause_time.is_none();
-        self.pause_time = self.pause_time.or(Some(Instant::now()));
+        self.pause_time = self.pause_time.or(Some(time));
         affected
     }
 
     pub fn unpause(&mut self) -> bool {
+        self.unpause_at(Utc::now())
+    }
+
+    /// Resume the clock as of `time`.  Returns whether the clock was paused.
+    pub fn unpause_at(&mut self, time: DateTime<Utc>) -> bool {
         let affected = self.pause_time.is_some();
         if let Some(pause_time) = self.pause_time {
-            self.total_time_paused += pause_time.elapsed();
+            self.total_time_paused += (time - pause_time).to_std().unwrap_or_default();
             self.pause_time = None;
         }
         affected
     }
 
+    /// Add `delta` to the time limit, or remove it if negative
+    pub fn adjust(&mut self, delta: TimeDelta) {
+        self.time_adjustment += delta;
+    }
+
+    /// Time given to the team `id` on top of the time limit
+    pub fn team_offset(&self, id: &UserId) -> TimeDelta {
+        self.team_offsets.get(id).copied().unwrap_or_default()
+    }
+
+    /// Set the time given to the team `id` on top of the time limit
+    pub fn set_team_offset(&mut self, id: UserId, offset: TimeDelta) {
+        if offset.is_zero() {
+            self.team_offsets.remove(&id);
+        } else {
+            self.team_offsets.insert(id, offset);
+        }
+    }
+
+    /// The largest time given to any team, which determines when the competition is over for
+    /// everybody
+    pub fn latest_team_offset(&self) -> TimeDelta {
+        self.team_offsets
+            .values()
+            .copied()
+            .max()
+            .unwrap_or_default()
+            .max(TimeDelta::zero())
+    }
+
     pub fn is_paused(&self) -> bool {
         self.pause_time.is_some()
     }
 
+    /// Whether the clock has ever been running.  A new clock is paused from the moment it was
+    /// created until the competition is started.
+    pub fn has_started(&self) -> bool {
+        self.pause_time != Some(self.start_time) || !self.total_time_paused.is_zero()
+    }
+
+    /// Time as of which scores are shown while the scoreboard is frozen, to the precision with
+    /// which submission times are stored
+    pub fn freeze_cutoff(&self) -> Option<OffsetDateTime> {
+        OffsetDateTime::from_unix_timestamp(self.frozen_at?.timestamp()).ok()
+    }
+
     pub fn current_time(&self) -> anyhow::Result<CurrentTime> {
-        match self.pause_time {
-            Some(pause_time) => Ok(CurrentTime {
-                paused: true,
-                duration: self
-                    .start_time
-                    .checked_add(pause_time.elapsed())
-                    .context("Failed to add pause duration to start time")?
-                    .checked_add(self.total_time_paused)
-                    .context("Failed to add total pause duration to start time")?
-                    .elapsed(),
-            }),
-            None => {
-                let duration = self
-                    .start_time
-                    .checked_add(self.total_time_paused)
-                    .context("Failed to add pause duration to start time")?
-                    .elapsed();
-                Ok(CurrentTime {
-                    paused: false,
-                    duration,
-                })
-            }
-        }
+        self.time_at(Utc::now())
+    }
+
+    /// Get the time that the clock showed at `now`
+    pub fn time_at(&self, now: DateTime<Utc>) -> anyhow::Result<CurrentTime> {
+        let end = self.pause_time.unwrap_or(now);
+        let total_time_paused = TimeDelta::from_std(self.total_time_paused)
+            .context("Total pause duration is out of range")?;
+        let duration = (end - self.start_time - total_time_paused)
+            .to_std()
+            .unwrap_or_default();
+        Ok(CurrentTime {
+            paused: self.is_paused(),
+            duration,
+            time_adjustment: self.time_adjustment,
+        })
+    }
+
+    pub fn current_team_time(&self, id: &UserId) -> anyhow::Result<CurrentTime> {
+        self.team_time_at(Utc::now(), id)
+    }
+
+    /// Get the time that the clock showed at `now` for the team `id`, taking the time given to the
+    /// team into account
+    pub fn team_time_at(&self, now: DateTime<Utc>, id: &UserId) -> anyhow::Result<CurrentTime> {
+        let mut time = self.time_at(now)?;
+        time.time_adjustment += self.team_offset(id);
+        Ok(time)
     }
 }
 
 impl CurrentTime {
+    /// Apply the adjustments made by hosts to the time limit of the competition
+    pub fn time_limit(&self, time_limit: Duration) -> Duration {
+        let adjustment = self.time_adjustment.abs().to_std().unwrap_or_default();
+        if self.time_adjustment < TimeDelta::zero() {
+            time_limit.saturating_sub(adjustment)
+        } else {
+            time_limit + adjustment
+        }
+    }
+
     pub fn time_left(self, time_limit: Duration) -> Duration {
-        time_limit
+        self.time_limit(time_limit)
             .checked_sub(self.duration)
             .unwrap_or(Duration::from_secs(0))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paused_time_is_excluded() {
+        let start = Utc::now();
+        let mut clock = ClockInfo {
+            start_time: start,
+            pause_time: Some(start),
+            total_time_paused: Duration::ZERO,
+            time_adjustment: TimeDelta::zero(),
+            team_offsets: HashMap::new(),
+            frozen_at: None,
+        };
+
+        assert!(!clock.has_started());
+        assert!(clock.unpause_at(start + TimeDelta::minutes(5)));
+        assert!(clock.has_started());
+        assert!(!clock.unpause_at(start + TimeDelta::minutes(6)));
+        assert_eq!(clock.total_time_paused, Duration::from_secs(5 * 60));
+
+        assert!(clock.pause_at(start + TimeDelta::minutes(15)));
+        let now = clock.time_at(start + TimeDelta::minutes(30)).unwrap();
+        assert!(now.paused);
+        assert_eq!(now.duration, Duration::from_secs(10 * 60));
+        assert_eq!(
+            now.time_left(Duration::from_secs(60 * 60)),
+            Duration::from_secs(50 * 60)
+        );
+    }
+
+    #[test]
+    fn adjustments_change_time_left() {
+        let start = Utc::now();
+        let mut clock = ClockInfo {
+            start_time: start,
+            pause_time: None,
+            total_time_paused: Duration::ZERO,
+            time_adjustment: TimeDelta::zero(),
+            team_offsets: HashMap::new(),
+            frozen_at: None,
+        };
+        let time_limit = Duration::from_secs(60 * 60);
+        let now = start + TimeDelta::minutes(50);
+
+        clock.adjust(TimeDelta::minutes(10));
+        let time_left = clock.time_at(now).unwrap().time_left(time_limit);
+        assert_eq!(time_left, Duration::from_secs(20 * 60));
+
+        clock.adjust(TimeDelta::minutes(-30));
+        let time_left = clock.time_at(now).unwrap().time_left(time_limit);
+        assert_eq!(time_left, Duration::ZERO);
+
+        clock.adjust(TimeDelta::hours(-2));
+        let current = clock.time_at(now).unwrap();
+        assert_eq!(current.time_limit(time_limit), Duration::ZERO);
+    }
+
+    #[test]
+    fn team_offsets() {
+        let start = Utc::now();
+        let mut clock = ClockInfo {
+            start_time: start,
+            pause_time: None,
+            total_time_paused: Duration::ZERO,
+            time_adjustment: TimeDelta::minutes(5),
+            team_offsets: HashMap::new(),
+            frozen_at: None,
+        };
+        let late = UserId::new();
+        let early = UserId::new();
+        let time_limit = Duration::from_secs(60 * 60);
+        let now = start + TimeDelta::minutes(60);
+
+        clock.set_team_offset(late, TimeDelta::minutes(15));
+        clock.set_team_offset(early, TimeDelta::minutes(-5));
+        assert_eq!(clock.latest_team_offset(), TimeDelta::minutes(15));
+
+        let time_left = |id| clock.team_time_at(now, id).unwrap().time_left(time_limit);
+        assert_eq!(time_left(&late), Duration::from_secs(20 * 60));
+        assert_eq!(time_left(&early), Duration::ZERO);
+        assert_eq!(time_left(&UserId::new()), Duration::from_secs(5 * 60));
+
+        clock.set_team_offset(late, TimeDelta::zero());
+        assert!(!clock.team_offsets.contains_key(&late));
+        assert_eq!(clock.latest_team_offset(), TimeDelta::zero());
+    }
+}
@@ -6,15 +6,31 @@ use serde::Serialize;
 use tracing::info;
 
 use crate::repositories::users::UserId;
+use crate::server::standings::Standing;
 use crate::server::AppState;
 use crate::services::ws::TestResultSend;
 
 #[derive(Clone, Debug, Serialize)]
 #[serde(tag = "kind")]
 pub enum ServerEvent {
-    // Unused
+    /// A team has solved every problem
     #[serde(rename_all = "camelCase")]
     OnComplete { id: UserId, time: DateTime<Utc> },
+    /// Time has run out, so the competition is over
+    #[serde(rename_all = "camelCase")]
+    OnContestEnd {
+        /// Standings of every team as shown to competitors, excluding results hidden by the
+        /// scoreboard freeze
+        standings: Vec<Standing>,
+        time: DateTime<Utc>,
+    },
+    /// The resolver has revealed every result hidden by the scoreboard freeze
+    #[serde(rename_all = "camelCase")]
+    OnResolved {
+        /// Final standings of every team
+        standings: Vec<Standing>,
+        time: DateTime<Utc>,
+    },
     #[serde(rename_all = "camelCase")]
     OnPause {
         paused_by: UserId,
@@ -26,6 +42,14 @@ pub enum ServerEvent {
         time: DateTime<Utc>,
     },
     #[serde(rename_all = "camelCase")]
+    OnTimeAdjusted {
+        adjusted_by: UserId,
+        /// Seconds added to the time limit, negative if time was removed
+        adjustment_in_seconds: i64,
+        time_left_in_seconds: u64,
+        time: DateTime<Utc>,
+    },
+    #[serde(rename_all = "camelCase")]
     OnTestEvaluation {
         id: UserId,
         question_idx: u32,
@@ -69,8 +93,11 @@ impl ServerEvent {
     pub fn get_fn_name(&self) -> &'static str {
         match self {
             ServerEvent::OnComplete { .. } => "onComplete",
+            ServerEvent::OnContestEnd { .. } => "onContestEnd",
+            ServerEvent::OnResolved { .. } => "onResolved",
             ServerEvent::OnPause { .. } => "onPause",
             ServerEvent::OnUnpause { .. } => "onUnpause",
+            ServerEvent::OnTimeAdjusted { .. } => "onTimeAdjusted",
             ServerEvent::OnTestEvaluation { .. } => "onTestEvaluation",
             ServerEvent::OnSubmissionEvaluation { .. } => "onSubmissionEvaluation",
             ServerEvent::OnTeamKick { .. } => "onTeamKick",
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 use tokio::sync::mpsc;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 use tracing::{error, trace};
 
 use super::events::ServerEvent;
@@ -18,32 +19,34 @@ impl EventHookHandler {
         (Self { rx }, tx)
     }
 
-    /// Begin handling events sent over the channel
+    /// Begin handling events sent over the channel, until `shutdown` is cancelled and all queued
+    /// events have been handled.
     ///
     /// Each event is handled in a separate thread. Panics
     /// are recovered from gracefully.
-    pub async fn start(&mut self) {
-        loop {
-            if let Some((event, state)) = self.rx.recv().await {
-                trace!("received event");
-                let state = state.clone();
-                tokio::spawn(async move {
-                    let (eval, rx) = create_evaluation_context();
-                    eval.start(event, state);
-                    match rx.await {
-                        Ok(Ok(_)) => {
-                            trace!("Successfully handled event");
-                        }
-                        Ok(Err(e)) => {
-                            error!("Error handling event: {:?}", e);
-                        }
-                        Err(e) => {
-                            error!("Error receiving value from eval sender: {:?}", e);
-                        }
-                    };
-                });
-            };
+    pub async fn start(&mut self, shutdown: CancellationToken) {
+        let tasks = TaskTracker::new();
+        while let Some((event, state)) = super::next_event(&mut self.rx, &shutdown).await {
+            trace!("received event");
+            tasks.spawn(async move {
+                let (eval, rx) = create_evaluation_context();
+                eval.start(event, state);
+                match rx.await {
+                    Ok(Ok(_)) => {
+                        trace!("Successfully handled event");
+                    }
+                    Ok(Err(e)) => {
+                        error!("Error handling event: {:?}", e);
+                    }
+                    Err(e) => {
+                        error!("Error receiving value from eval sender: {:?}", e);
+                    }
+                };
+            });
         }
+
+        tasks.close();
+        tasks.wait().await;
     }
 }
 
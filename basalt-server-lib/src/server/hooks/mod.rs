@@ -3,3 +3,92 @@ pub mod events;
 pub mod handlers;
 #[cfg(feature = "webhooks")]
 pub mod webhooks;
+
+/// Receive the next event for a handler.
+///
+/// Once `shutdown` is cancelled, the channel is closed so that no new events are accepted, but
+/// events which have already been queued are still returned.  `None` is returned once the queue
+/// is empty.
+#[cfg(any(feature = "scripting", feature = "webhooks"))]
+async fn next_event(
+    rx: &mut tokio::sync::mpsc::UnboundedReceiver<(
+        events::ServerEvent,
+        std::sync::Arc<super::AppState>,
+    )>,
+    shutdown: &tokio_util::sync::CancellationToken,
+) -> Option<(events::ServerEvent, std::sync::Arc<super::AppState>)> {
+    tokio::select! {
+        biased;
+        event = rx.recv() => event,
+        _ = shutdown.cancelled() => {
+            rx.close();
+            rx.recv().await
+        }
+    }
+}
+
+#[cfg(all(test, any(feature = "scripting", feature = "webhooks")))]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use bedrock::Config;
+    use tokio::task::JoinSet;
+
+    use crate::{
+        repositories::users::Role,
+        server::{tester, AppState},
+        testing::{db_user, mock_db, SAMPLE_1},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn events_of_drained_tests_are_delivered() {
+        let db = mock_db().await;
+        let team = db_user(&db, "team", Role::Competitor).await;
+        let cfg = Config::from_str(SAMPLE_1, Some("single.toml")).unwrap();
+        let mut state = AppState::new(db, cfg, None);
+        state.init().await.unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        state.dispatchers.push(tx);
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut handlers = JoinSet::new();
+        let shutdown = state.hooks_shutdown.clone();
+        let events = Arc::clone(&received);
+        handlers.spawn(async move {
+            while let Some((event, _)) = next_event(&mut rx, &shutdown).await {
+                events.lock().unwrap().push(event);
+            }
+        });
+        let state = Arc::new(state);
+
+        // slow enough that the tests are still running once the server starts shutting down
+        let code = "import sys, time\ntime.sleep(0.2)\nprint(sys.stdin.read().strip()[::-1])";
+        let created = tester::run_test(
+            Arc::clone(&state),
+            "python3".into(),
+            0,
+            code.into(),
+            false,
+            team.id,
+        )
+        .await
+        .unwrap();
+        state.shutdown.cancel();
+        state
+            .finish_shutdown(handlers, Duration::from_secs(30))
+            .await;
+
+        let evaluated = received
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| matches!(e, events::ServerEvent::OnSubmissionEvaluation { .. }))
+            .count();
+        assert_eq!(evaluated, created.cases as usize);
+    }
+}
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 use tokio::{sync::mpsc, task::JoinSet};
+use tokio_util::sync::CancellationToken;
 use tracing::{error, trace};
 
 use super::events::ServerEvent;
@@ -17,39 +18,38 @@ impl EventWebhookHandler {
         (Self { rx }, tx)
     }
 
-    /// Begin handling events sent over the channel
+    /// Begin handling events sent over the channel, until `shutdown` is cancelled and all queued
+    /// events have been handled.
     ///
     /// Each event is handled in a separate thread. Panics
     /// are recovered from gracefully.
-    pub async fn start(&mut self) {
+    pub async fn start(&mut self, shutdown: CancellationToken) {
         // Create a single client instance to reuse across events
         let client = reqwest::Client::new();
 
-        loop {
-            if let Some((event, state)) = self.rx.recv().await {
-                let webhooks = &state.config.integrations.webhooks;
-                let mut join_set = JoinSet::new();
-
-                for webhook_url in webhooks {
-                    let client = client.clone();
-                    let event = event.clone();
-                    let url = webhook_url.clone();
-                    let url_str = url.to_string();
-
-                    join_set.spawn(async move {
-                        match client.post(url).json(&event).send().await {
-                            Ok(r) => {
-                                trace!("Published event to {} with status {}", url_str, r.status())
-                            }
-                            Err(e) => {
-                                error!("Error publishing event to {}, {:?}", url_str, e)
-                            }
+        while let Some((event, state)) = super::next_event(&mut self.rx, &shutdown).await {
+            let webhooks = &state.config.integrations.webhooks;
+            let mut join_set = JoinSet::new();
+
+            for webhook_url in webhooks {
+                let client = client.clone();
+                let event = event.clone();
+                let url = webhook_url.clone();
+                let url_str = url.to_string();
+
+                join_set.spawn(async move {
+                    match client.post(url).json(&event).send().await {
+                        Ok(r) => {
+                            trace!("Published event to {} with status {}", url_str, r.status())
+                        }
+                        Err(e) => {
+                            error!("Error publishing event to {}, {:?}", url_str, e)
                         }
-                    });
-                }
+                    }
+                });
+            }
 
-                join_set.join_all().await;
-            };
+            join_set.join_all().await;
         }
     }
 }
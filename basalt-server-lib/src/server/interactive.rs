@@ -0,0 +1,239 @@
+use std::{
+    borrow::Cow,
+    path::PathBuf,
+    process::{ExitStatus, Stdio},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use bedrock::packet::Test;
+use leucite::CommandExt;
+use serde::Deserialize;
+use tokio::process::Command;
+
+use crate::{
+    repositories::submissions::{NewTestResults, TestResultState},
+    server::{
+        limits::Cgroup,
+        runner::{self, exit_code, relay, Compiled},
+        sandbox::{system_rules, WorkDir},
+    },
+};
+
+/// Exit code of a process that was killed by `SIGPIPE`, which happens to a solution that keeps
+/// writing after the interactor has reached its verdict
+const SIGPIPE: i32 = 13;
+
+/// A program which talks to submissions to an interactive problem and decides whether they are
+/// correct.
+///
+/// The command is run with `bash -c` from the directory containing the settings file, in a
+/// sandbox like that of solutions, and is passed the paths of files containing the input and the
+/// expected output of the test as its arguments.  Its standard input is the output of the
+/// solution and its standard output is sent to the solution.  The solution passes the test if the
+/// interactor exits successfully.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Interactor {
+    pub command: String,
+    /// Directory from which the command is run, set once the settings have been read.  The
+    /// current directory is used if it is not set.
+    #[serde(skip)]
+    pub dir: Option<PathBuf>,
+}
+
+impl Interactor {
+    /// Run a compiled solution against the interactor for a single test.  Only the solution is
+    /// run in a cgroup of its own, since the interactor is part of the problem.
+    ///
+    /// The recorded output of the test is everything that the solution sent to the interactor.
+    pub async fn run(
+        &self,
+        solution: &Compiled,
+        test: &Test,
+        timeout: Duration,
+    ) -> anyhow::Result<NewTestResults<'static>> {
+        let files = WorkDir::new("interactor").await?;
+        let mut paths = Vec::with_capacity(2);
+        for (file, content) in [("input", &test.input), ("expected", &test.output)] {
+            let path = files.path().join(file);
+            tokio::fs::write(&path, content)
+                .await
+                .with_context(|| format!("Writing {} for interactor", file))?;
+            paths.push(path);
+        }
+
+        let limits = *solution.limits();
+        let cgroup = limits.cgroup()?;
+        let mut solution = solution.spawn(cgroup.as_ref(), false)?;
+        let start = Instant::now();
+
+        // the interactor is run from the directory containing the settings, so that it can refer
+        // to files next to them
+        let cwd = match &self.dir {
+            Some(dir) => dir.clone(),
+            None => std::env::current_dir().context("Getting current directory")?,
+        };
+        let rules = system_rules()
+            .add_read_only(&cwd)
+            .add_read_write(files.path());
+        let mut interactor = Command::new("bash")
+            .arg("-c")
+            .arg(&self.command)
+            .arg("interactor")
+            .args(&paths)
+            .current_dir(&cwd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .restrict(Arc::new(rules))
+            .spawn()
+            .context("Spawning interactor")?;
+
+        let (Some(solution_in), Some(solution_out), Some(solution_err)) = (
+            solution.stdin.take(),
+            solution.stdout.take(),
+            solution.stderr.take(),
+        ) else {
+            anyhow::bail!("Solution is missing a standard stream");
+        };
+        let (Some(interactor_in), Some(interactor_out)) =
+            (interactor.stdin.take(), interactor.stdout.take())
+        else {
+            anyhow::bail!("Interactor is missing a standard stream");
+        };
+
+        let output_bytes = limits.output_bytes();
+        let mut transcript = tokio::spawn(relay(solution_out, interactor_in, output_bytes));
+        let mut replies = tokio::spawn(relay(interactor_out, solution_in, usize::MAX));
+        // stderr is limited like the output, so that it cannot exhaust the memory of the server
+        let mut stderr = tokio::spawn(relay(solution_err, tokio::io::sink(), output_bytes));
+
+        let interaction = async {
+            let (solution, interactor) = tokio::join!(
+                async {
+                    let status = solution.wait().await;
+                    (status, start.elapsed())
+                },
+                interactor.wait()
+            );
+            let transcript = (&mut transcript).await?;
+            let stderr = (&mut stderr).await?;
+            anyhow::Ok((solution, interactor?, transcript, stderr))
+        };
+
+        let Ok(outcome) = tokio::time::timeout(timeout, interaction).await else {
+            // both programs are killed when they are dropped
+            transcript.abort();
+            replies.abort();
+            stderr.abort();
+            return Ok(runner::timed_out(timeout));
+        };
+        replies.abort();
+        let ((solution, time_taken), interactor, transcript, stderr) = outcome?;
+        let solution = solution.context("Waiting for solution")?;
+        let transcript = transcript?;
+        let stderr = String::from_utf8_lossy(&stderr?).into_owned();
+
+        Ok(NewTestResults {
+            result: limits.classify(
+                verdict(solution, interactor),
+                transcript.len(),
+                exit_code(solution),
+                cgroup.as_ref().is_some_and(Cgroup::out_of_memory),
+            ),
+            stdout: Cow::Owned(String::from_utf8_lossy(&transcript).into_owned()),
+            stderr: Cow::Owned(stderr),
+            exit_status: exit_code(solution),
+            time_taken: time_taken.into(),
+        })
+    }
+}
+
+/// Decide the state of a test from how the solution and the interactor exited.  A solution which
+/// crashes fails at runtime, otherwise the interactor decides.
+fn verdict(solution: ExitStatus, interactor: ExitStatus) -> TestResultState {
+    let crashed = !solution.success() && exit_code(solution) != 128 + SIGPIPE;
+    if crashed {
+        TestResultState::RuntimeFail
+    } else if interactor.success() {
+        TestResultState::Pass
+    } else {
+        TestResultState::IncorrectOutput
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+
+    use bedrock::Config;
+
+    use crate::{server::limits::Limits, settings::Settings, testing::SAMPLE_1};
+
+    use super::*;
+
+    #[test]
+    fn verdicts() {
+        let exited = |code: i32| ExitStatus::from_raw(code << 8);
+        let killed = ExitStatus::from_raw;
+
+        assert_eq!(verdict(exited(0), exited(0)), TestResultState::Pass);
+        assert_eq!(
+            verdict(exited(0), exited(1)),
+            TestResultState::IncorrectOutput
+        );
+        assert_eq!(verdict(exited(1), exited(0)), TestResultState::RuntimeFail);
+        // the interactor stopped listening to a solution after rejecting it
+        assert_eq!(
+            verdict(killed(SIGPIPE), exited(1)),
+            TestResultState::IncorrectOutput
+        );
+    }
+
+    /// Judge `code` in python against the first test of the sample packet, with an interactor
+    /// which sends the input to the solution and accepts it if it replies with the expected output
+    async fn interact(code: &str) -> NewTestResults<'static> {
+        let config = Config::from_str(SAMPLE_1, Some("single.toml")).unwrap();
+        let language = config.languages.get_by_str("python3").unwrap();
+        let limits = Settings::default().limits(&config, 0, Some("python3"));
+        let interactor = Interactor {
+            command: r#"echo "$(cat "$1")"; read -r answer; [ "$answer" = "$(cat "$2")" ]"#.into(),
+            dir: None,
+        };
+
+        let compiled = runner::compile(
+            language,
+            code,
+            Limits::compile(&config),
+            Duration::from_secs(10),
+            Limits::run(&config, &limits),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        interactor
+            .run(
+                &compiled,
+                &config.packet.problems[0].tests[0],
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn solutions_are_judged_by_the_interactor() {
+        let passed = interact("print(input()[::-1])").await;
+        assert_eq!(passed.result, TestResultState::Pass);
+        assert_eq!(passed.stdout, "olleh\n");
+
+        let wrong = interact("print(input())").await;
+        assert_eq!(wrong.result, TestResultState::IncorrectOutput);
+
+        let slow = interact("import time\ninput()\ntime.sleep(10)").await;
+        assert_eq!(slow.result, TestResultState::TimedOut);
+    }
+}
@@ -0,0 +1,384 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{bail, Context};
+use bedrock::Config;
+use rand::{distributions::Alphanumeric, Rng};
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use crate::{repositories::submissions::TestResultState, settings::ProblemLimits};
+
+/// Maximum number of processes, including threads, that each test may have running.  This is
+/// only enforced when tests are run in cgroups, see [`cgroups`].
+const MAX_PROCESSES: u64 = 256;
+/// Maximum number of processes that may be running as the user that the server runs as.  Linux
+/// counts `ulimit -u` across every process of the user rather than each test, so this is generous
+/// and mainly stops fork bombs when tests cannot be run in cgroups.
+const MAX_USER_PROCESSES: u64 = 1024;
+/// Signal with which a process is killed once it writes a file that is too large
+const SIGXFSZ: i32 = 25;
+/// Controllers which must be available to run tests in cgroups
+const CONTROLLERS: &[&str] = &["memory", "pids"];
+
+/// Resource limits under which submissions are compiled or run.
+///
+/// If the server has been delegated a cgroup (v2), every test is run in a cgroup of its own so
+/// that its memory and processes are limited and accounted for separately from other tests, see
+/// [`Limits::cgroup`].  Otherwise, memory is limited with `ulimit` and running out of it is
+/// reported as a runtime failure.  Either way, the shell that runs the command also applies
+/// `ulimit`, in addition to the sandbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum memory in KiB.  With cgroups, this is the memory of every process of the test
+    /// together, otherwise it is the size of the data segment of each process, which includes its
+    /// heap.
+    pub memory: u64,
+    /// Maximum size of any file that is written, and of the output of the program, in KiB
+    pub file_size: u64,
+    /// Maximum number of processes, including threads, of each test when it is run in a cgroup
+    pub processes: u64,
+}
+
+impl Limits {
+    /// Limits for compiling submissions, from `max_memory.compile` (in MiB) and `max_file_size`
+    /// (in KiB) in the `[test_runner]` section of the configuration
+    pub fn compile(config: &Config) -> Self {
+        Self {
+            memory: config.test_runner.max_memory.compile * 1024,
+            file_size: config.test_runner.max_file_size,
+            processes: MAX_PROCESSES,
+        }
+    }
+
+    /// Limits for running submissions against the tests of a problem, from the memory limit of the
+    /// problem and `max_file_size` (in KiB) in the `[test_runner]` section of the configuration
+    pub fn run(config: &Config, problem: &ProblemLimits) -> Self {
+        Self {
+            memory: problem.memory * 1024,
+            file_size: config.test_runner.max_file_size,
+            processes: MAX_PROCESSES,
+        }
+    }
+
+    /// Maximum number of bytes that a program may write to its standard output
+    pub fn output_bytes(&self) -> usize {
+        usize::try_from(self.file_size * 1024).unwrap_or(usize::MAX)
+    }
+
+    /// Wrap a shell command so that it runs within these limits.  With cgroups, memory and
+    /// processes are only limited once the command has joined the cgroup of the test.
+    pub fn wrap(&self, command: &str) -> String {
+        match cgroups() {
+            Some(_) => format!(
+                "ulimit -f {} -u {} || exit 1\n{}",
+                self.file_size, MAX_USER_PROCESSES, command
+            ),
+            None => format!(
+                "ulimit -d {} -f {} -u {} || exit 1\n{}",
+                self.memory, self.file_size, MAX_USER_PROCESSES, command
+            ),
+        }
+    }
+
+    /// Like [`Limits::wrap`], but the output of the command is also cut off just after the limit,
+    /// so that a program which prints too much cannot exhaust the memory of the server
+    pub fn wrap_output(&self, command: &str) -> String {
+        self.wrap(&format!(
+            "set -o pipefail\n{{\n{}\n}} | head -c {}",
+            command,
+            self.output_bytes() + 1
+        ))
+    }
+
+    /// Decide whether a test which did not pass failed because it exceeded these limits, given
+    /// the number of bytes that it printed, its exit status and whether its cgroup ran out of
+    /// memory, see [`Cgroup::out_of_memory`]
+    pub fn classify(
+        &self,
+        state: TestResultState,
+        stdout_len: usize,
+        exit_status: i32,
+        out_of_memory: bool,
+    ) -> TestResultState {
+        match state {
+            TestResultState::Pass => state,
+            _ if stdout_len > self.output_bytes() || exit_status == 128 + SIGXFSZ => {
+                TestResultState::OutputLimitExceeded
+            }
+            TestResultState::RuntimeFail if out_of_memory => TestResultState::MemoryLimitExceeded,
+            state => state,
+        }
+    }
+
+    /// Create a cgroup in which a single program is run within these limits, or `None` if tests
+    /// cannot be run in cgroups.
+    ///
+    /// The cgroup is created and configured by the server, and programs only ever join it, see
+    /// [`Cgroup::join`], so that they cannot change their own limits or those of other tests.
+    pub fn cgroup(&self) -> anyhow::Result<Option<Cgroup>> {
+        let Some(parent) = cgroups() else {
+            return Ok(None);
+        };
+        remove_stale_cgroups();
+
+        let name: String = rand::thread_rng()
+            .sample_iter(Alphanumeric)
+            .take(12)
+            .map(char::from)
+            .collect();
+        let dir = parent.join(name);
+        std::fs::create_dir(&dir).context("Creating cgroup of test")?;
+
+        let configure = || {
+            std::fs::write(dir.join("memory.max"), (self.memory * 1024).to_string())
+                .context("Limiting memory of test")?;
+            std::fs::write(dir.join("pids.max"), self.processes.to_string())
+                .context("Limiting processes of test")?;
+            // fails if swap is not enabled, in which case there is nothing to limit
+            let _ = std::fs::write(dir.join("memory.swap.max"), "0");
+            File::options()
+                .write(true)
+                .open(dir.join("cgroup.procs"))
+                .context("Opening processes of test cgroup")
+        };
+        match configure() {
+            Ok(procs) => Ok(Some(Cgroup { dir, procs })),
+            Err(error) => {
+                let _ = std::fs::remove_dir(&dir);
+                Err(error)
+            }
+        }
+    }
+}
+
+/// A cgroup in which a single program is run, see [`Limits::cgroup`].  Any processes that are left
+/// in it are killed once it is dropped, such as those of a test which timed out.
+pub struct Cgroup {
+    dir: PathBuf,
+    /// `cgroup.procs` of the cgroup, opened by the server so that programs can join it without
+    /// having access to any file of the cgroup
+    procs: File,
+}
+
+impl Cgroup {
+    /// Move the process spawned by `command` into this cgroup before it runs the program.
+    ///
+    /// This must be called before the sandbox is applied to `command`, so that the process joins
+    /// the cgroup before it is restricted.
+    pub fn join(&self, command: &mut Command) -> anyhow::Result<()> {
+        // the duplicate is closed when the program is executed, so the program cannot write to it
+        let procs = self.procs.try_clone().context("Duplicating cgroup.procs")?;
+        // SAFETY: this only writes to a file which is already open, which is a single system call
+        // and safe to do between forking and executing the program
+        unsafe {
+            command.pre_exec(move || (&procs).write_all(b"0"));
+        }
+        Ok(())
+    }
+
+    /// Whether the kernel killed any process in the cgroup for using more memory than the limit.
+    /// This is read by the server rather than reported by the program, so it cannot be forged.
+    pub fn out_of_memory(&self) -> bool {
+        std::fs::read_to_string(self.dir.join("memory.events")).is_ok_and(|events| {
+            events.lines().any(|line| {
+                line.strip_prefix("oom_kill ")
+                    .and_then(|count| count.trim().parse::<u64>().ok())
+                    .is_some_and(|count| count > 0)
+            })
+        })
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        let _ = std::fs::write(self.dir.join("cgroup.kill"), "1");
+        // fails until every process has exited, in which case it is removed later
+        if std::fs::remove_dir(&self.dir).is_err() {
+            stale_cgroups().push(std::mem::take(&mut self.dir));
+        }
+    }
+}
+
+/// Cgroups of tests which could not be removed yet since some of their processes were still
+/// exiting
+fn stale_cgroups() -> std::sync::MutexGuard<'static, Vec<PathBuf>> {
+    static STALE: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+    STALE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn remove_stale_cgroups() {
+    stale_cgroups().retain(|dir| {
+        let _ = std::fs::write(dir.join("cgroup.kill"), "1");
+        std::fs::remove_dir(dir).is_err()
+    });
+}
+
+/// Directory of the cgroup in which every test is run in a cgroup of its own, or `None` if tests
+/// cannot be run in cgroups.
+///
+/// This requires the server to have been delegated a cgroup (v2) with the memory and pids
+/// controllers, for example with `Delegate=yes` in a systemd service.  Processes may only be in
+/// the leaves of the hierarchy, so the server moves itself into `basalt-server` within its cgroup,
+/// and tests are run within `basalt-tests` next to it.
+pub fn cgroups() -> Option<&'static Path> {
+    static CGROUPS: OnceLock<Option<PathBuf>> = OnceLock::new();
+    CGROUPS
+        .get_or_init(|| match delegate_cgroups() {
+            Ok(dir) => {
+                info!(dir = %dir.display(), "Running tests in cgroups");
+                Some(dir)
+            }
+            Err(error) => {
+                warn!(
+                    ?error,
+                    "Cannot run tests in cgroups, so running out of memory is reported as a \
+                     runtime failure and the process limit applies to the whole user"
+                );
+                None
+            }
+        })
+        .as_deref()
+}
+
+fn delegate_cgroups() -> anyhow::Result<PathBuf> {
+    let own = std::fs::read_to_string("/proc/self/cgroup").context("Reading cgroup of server")?;
+    let own = own
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .context("Server is not in a cgroup v2 hierarchy")?;
+    let dir = Path::new("/sys/fs/cgroup").join(own.trim_start_matches('/'));
+
+    let controllers = std::fs::read_to_string(dir.join("cgroup.controllers"))
+        .context("Reading available controllers")?;
+    for controller in CONTROLLERS {
+        if !controllers.split_whitespace().any(|c| c == *controller) {
+            bail!("The {} controller is not available", controller);
+        }
+    }
+
+    let enable = CONTROLLERS
+        .iter()
+        .map(|c| format!("+{}", c))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let server = dir.join("basalt-server");
+    std::fs::create_dir_all(&server).context("Creating cgroup of server")?;
+    std::fs::write(server.join("cgroup.procs"), std::process::id().to_string())
+        .context("Moving server into its cgroup")?;
+    std::fs::write(dir.join("cgroup.subtree_control"), &enable).context("Enabling controllers")?;
+
+    let tests = dir.join("basalt-tests");
+    std::fs::create_dir_all(&tests).context("Creating cgroup of tests")?;
+    std::fs::write(tests.join("cgroup.subtree_control"), &enable)
+        .context("Enabling controllers for tests")?;
+
+    // tests which were still running when the server last stopped
+    for entry in std::fs::read_dir(&tests).context("Reading cgroups of tests")? {
+        let path = entry.context("Reading cgroups of tests")?.path();
+        if path.is_dir() {
+            let _ = std::fs::write(path.join("cgroup.kill"), "1");
+            stale_cgroups().push(path);
+        }
+    }
+    Ok(tests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: Limits = Limits {
+        memory: 64 * 1024,
+        file_size: 1,
+        processes: 256,
+    };
+
+    #[test]
+    fn classify() {
+        let classify = |state, stdout_len, exit_status, out_of_memory| {
+            LIMITS.classify(state, stdout_len, exit_status, out_of_memory)
+        };
+        assert_eq!(
+            classify(TestResultState::RuntimeFail, 1025, 1, false),
+            TestResultState::OutputLimitExceeded
+        );
+        assert_eq!(
+            classify(TestResultState::IncorrectOutput, 1025, 0, false),
+            TestResultState::OutputLimitExceeded
+        );
+        assert_eq!(
+            classify(TestResultState::RuntimeFail, 10, 137, true),
+            TestResultState::MemoryLimitExceeded
+        );
+        assert_eq!(
+            classify(TestResultState::RuntimeFail, 10, 1, false),
+            TestResultState::RuntimeFail
+        );
+        assert_eq!(
+            classify(TestResultState::TimedOut, 10, -1, false),
+            TestResultState::TimedOut
+        );
+        assert_eq!(
+            classify(TestResultState::RuntimeFail, 0, 128 + SIGXFSZ, false),
+            TestResultState::OutputLimitExceeded
+        );
+    }
+
+    /// Run `command` within [`LIMITS`], in a cgroup if tests are run in cgroups, returning its
+    /// output and whether it ran out of memory
+    async fn bash(command: String) -> (std::process::Output, bool) {
+        let cgroup = LIMITS.cgroup().unwrap();
+        let mut bash = Command::new("bash");
+        bash.arg("-c").arg(command);
+        if let Some(cgroup) = &cgroup {
+            cgroup.join(&mut bash).unwrap();
+        }
+        let output = bash.output().await.unwrap();
+        (output, cgroup.is_some_and(|c| c.out_of_memory()))
+    }
+
+    #[tokio::test]
+    async fn limits_are_enforced() {
+        let (output, _) = bash(LIMITS.wrap_output("yes")).await;
+        assert_eq!(output.stdout.len(), LIMITS.output_bytes() + 1);
+        assert!(!output.status.success());
+
+        let (output, _) = bash(LIMITS.wrap("ulimit -f; ulimit -u")).await;
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!("{}\n{}\n", LIMITS.file_size, MAX_USER_PROCESSES)
+        );
+
+        let (output, _) = match cgroups() {
+            Some(_) => {
+                let cgroup = r#""/sys/fs/cgroup$(sed -n 's/^0:://p' /proc/self/cgroup)""#;
+                let command = format!("cat {0}/memory.max {0}/pids.max", cgroup);
+                bash(LIMITS.wrap(&command)).await
+            }
+            None => bash(LIMITS.wrap("ulimit -d")).await,
+        };
+        let expected = match cgroups() {
+            Some(_) => format!("{}\n{}\n", LIMITS.memory * 1024, LIMITS.processes),
+            None => format!("{}\n", LIMITS.memory),
+        };
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    }
+
+    #[tokio::test]
+    async fn running_out_of_memory() {
+        // tail keeps the whole of the last line, which is all of the input, in memory
+        let command = LIMITS.wrap("head -c 256M /dev/zero | tail -n 1 > /dev/null");
+        let (output, out_of_memory) = bash(command).await;
+        assert!(!output.status.success());
+        assert_eq!(out_of_memory, cgroups().is_some());
+
+        // exiting like the kernel killed the program does not count
+        let (_, out_of_memory) = bash(LIMITS.wrap("kill -9 $$")).await;
+        assert!(!out_of_memory);
+    }
+}
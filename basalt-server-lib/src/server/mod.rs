@@ -1,27 +1,47 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
 use axum::Router;
-use bedrock::Config;
+use bedrock::{Config, Game, PointsSettings};
+use chrono::Utc;
 use clock::ClockInfo;
 use dashmap::DashSet;
 use rand::{distributions::Alphanumeric, Rng};
 use teams::TeamManagement;
+use time::OffsetDateTime;
 use tokio::{
     sync::{mpsc::UnboundedSender, RwLock},
     task::JoinSet,
 };
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
 use websocket::WebSocketManager;
 
+pub mod checker;
 pub mod clock;
 pub mod hooks;
+pub mod interactive;
+pub mod limits;
+pub mod resolver;
+pub mod runner;
+pub mod sandbox;
+pub mod scheduler;
+pub mod standings;
 pub mod teams;
 pub mod tester;
 pub mod websocket;
 
 use crate::{
-    repositories::{self, users::Role},
+    repositories::{
+        self,
+        submissions::{SubmissionHistory, SubmissionId, SubmissionState},
+        users::{Role, User, UserId},
+    },
     server::{hooks::events::ServerEvent, tester::Tester},
-    services,
+    services::{
+        self,
+        ws::{Broadcast, WebSocketSend},
+    },
+    settings::Settings,
     storage::SqliteLayer,
 };
 
@@ -36,8 +56,19 @@ pub struct AppState {
     pub active_submissions: DashSet<(websocket::ConnectionKind, usize)>,
     pub tester: Tester,
     pub config: Config,
+    /// Server settings which are not part of the packet
+    pub settings: Settings,
     pub clock: RwLock<ClockInfo>,
+    /// Problems which were hidden until they were released and have since been released
+    pub released_problems: DashSet<usize>,
+    /// Submissions which are waiting to be judged again or are being judged again
+    pub rejudging: DashSet<SubmissionId>,
     pub dispatchers: Dispatchers,
+    /// Cancelled once the server starts shutting down, after which no new submissions are accepted
+    pub shutdown: CancellationToken,
+    /// Cancelled once running tests have been drained during shutdown, after which event handlers
+    /// stop once they have handled every queued event
+    pub hooks_shutdown: CancellationToken,
 }
 
 impl AppState {
@@ -50,16 +81,230 @@ impl AppState {
             active_tests: Default::default(),
             active_submissions: Default::default(),
             dispatchers: Default::default(),
-            tester: Tester::new(&config),
+            tester: Tester::new(&config, &Settings::default()),
             config,
+            settings: Default::default(),
             clock: Default::default(),
+            released_problems: Default::default(),
+            rejudging: Default::default(),
+            shutdown: Default::default(),
+            hooks_shutdown: Default::default(),
         }
     }
 
+    /// Use `settings` for the competition.  The tester is created again, since the settings
+    /// decide the limits under which each problem is run.
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.tester = Tester::new(&self.config, &settings);
+        self.settings = settings;
+    }
+
+    /// Length of the competition
+    pub fn time_limit(&self) -> Duration {
+        match &self.config.game {
+            Game::Points(PointsSettings { time_limit, .. }) => *time_limit,
+            Game::Race(_) => self.settings.race.time_limit(),
+        }
+    }
+
+    pub fn is_race(&self) -> bool {
+        matches!(self.config.game, Game::Race(_))
+    }
+
+    /// Whether competitors may see and submit solutions to the problem at `index`
+    pub fn is_released(&self, index: usize) -> bool {
+        match self.settings.problem(index) {
+            Some(problem) if problem.is_scheduled() => self.released_problems.contains(&index),
+            _ => true,
+        }
+    }
+
+    /// Release the problem at `index`, notifying everybody if it had not been released before
+    pub async fn release_problem(&self, index: usize) -> anyhow::Result<bool> {
+        let released = repositories::problems::release_problem(&self.db, index).await?;
+        self.released_problems.insert(index);
+        if released {
+            info!(index, "Releasing problem");
+            self.websocket
+                .broadcast(Broadcast::QuestionReleased { index });
+        }
+        Ok(released)
+    }
+
+    /// Send the state of `clock` to every connection, so that their countdowns match the server.
+    /// Each team is sent the time that it has left, which includes any time given to it alone.
+    pub fn broadcast_clock(&self, clock: &ClockInfo) {
+        let now = Utc::now();
+        let current = match clock.time_at(now) {
+            Ok(current) => current,
+            Err(error) => {
+                error!(?error, "Error getting the current time");
+                return;
+            }
+        };
+
+        let time_limit = self.time_limit();
+        self.websocket.broadcast_each(|who| {
+            let mut time = current;
+            if let Some(id) = who.user() {
+                time.time_adjustment += clock.team_offset(id);
+            }
+            Some(Broadcast::Clock {
+                is_paused: time.paused,
+                time_left_in_seconds: time.time_left(time_limit).as_secs(),
+                server_time: now,
+            })
+        });
+    }
+
+    /// Whether the clock has ever been started.  Until then, the problems are only shown to hosts.
+    pub async fn has_started(&self) -> bool {
+        self.clock.read().await.has_started()
+    }
+
     pub async fn is_paused(&self) -> bool {
         self.clock.read().await.is_paused()
     }
 
+    /// Whether the time limit has run out for every team
+    pub async fn is_game_over(&self) -> bool {
+        let clock = self.clock.read().await;
+        clock.current_time().is_ok_and(|mut t| {
+            t.time_adjustment += clock.latest_team_offset();
+            t.time_left(self.time_limit()).is_zero()
+        })
+    }
+
+    /// Whether the time limit has run out for the team `id`, which may have been given more time
+    /// than other teams
+    pub async fn is_game_over_for(&self, id: &UserId) -> bool {
+        self.clock
+            .read()
+            .await
+            .current_team_time(id)
+            .is_ok_and(|t| t.time_left(self.time_limit()).is_zero())
+    }
+
+    /// Time as of which scores are shown to `viewer`, or `None` if they should see live scores.
+    ///
+    /// While the scoreboard is frozen, only hosts see live scores.
+    pub async fn scoreboard_cutoff(&self, viewer: Option<&User>) -> Option<OffsetDateTime> {
+        if viewer.is_some_and(|u| u.role == Role::Host) {
+            return None;
+        }
+        self.clock.read().await.freeze_cutoff()
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_cancelled()
+    }
+
+    /// Wait up to `timeout` for running tests to finish.  Tests which do not finish in time are
+    /// aborted and their submissions are marked as failed.
+    pub async fn drain_tests(&self, timeout: Duration) {
+        if self.tester.drain(timeout).await {
+            return;
+        }
+
+        let aborted = self.tester.abort_all();
+        warn!(
+            count = aborted.len(),
+            "Tests did not finish in time, aborting"
+        );
+        // give the aborted tasks a moment to stop before updating their submissions
+        self.tester.drain(Duration::from_secs(1)).await;
+
+        for id in aborted {
+            let submission = match repositories::submissions::get_submission(&self.db, id).await {
+                Ok(Some(submission)) => submission,
+                Ok(None) => continue,
+                Err(error) => {
+                    error!(?error, ?id, "Error getting aborted submission");
+                    continue;
+                }
+            };
+            if submission.state != SubmissionState::Started {
+                continue;
+            }
+            if let Err(error) = repositories::submissions::PartialSubmissionHistory(submission)
+                .fail(&self.db)
+                .await
+            {
+                error!(?error, ?id, "Error marking aborted submission as failed");
+            }
+        }
+    }
+
+    /// Finish shutting down once no more requests are handled.  Running tests are given up to
+    /// `timeout` to finish, after which the event `handlers` are given up to `timeout` to handle
+    /// the events that were dispatched, including those of the tests that were drained.
+    pub async fn finish_shutdown(&self, handlers: JoinSet<()>, timeout: Duration) {
+        info!(?timeout, "Waiting for running tests to finish");
+        self.drain_tests(timeout).await;
+
+        self.hooks_shutdown.cancel();
+        if tokio::time::timeout(timeout, handlers.join_all())
+            .await
+            .is_err()
+        {
+            warn!("Event handlers did not finish in time");
+        }
+    }
+
+    /// Deal with submissions that were still being judged when the server last stopped.
+    ///
+    /// If `requeue` is set, they are judged again.  Otherwise, they are marked as failed, which
+    /// does not count against the team's attempts, and the team is notified when they next
+    /// connect.
+    pub async fn recover_submissions(self: &Arc<Self>, requeue: bool) -> anyhow::Result<()> {
+        let unfinished = repositories::submissions::get_unfinished_submissions(&self.db).await?;
+        if unfinished.is_empty() {
+            return Ok(());
+        }
+
+        info!(
+            count = unfinished.len(),
+            requeue, "Recovering submissions left unfinished by the last run"
+        );
+        for submission in unfinished {
+            let id = submission.id;
+            if requeue {
+                let state = Arc::clone(self);
+                tokio::spawn(async move {
+                    if tester::rejudge(Arc::clone(&state), submission.clone())
+                        .await
+                        .is_none()
+                    {
+                        warn!(?id, "Could not requeue submission, marking it as failed");
+                        state.fail_interrupted(submission).await;
+                    }
+                });
+            } else {
+                self.fail_interrupted(submission).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn fail_interrupted(&self, submission: SubmissionHistory) {
+        let id = submission.id;
+        let submitter = submission.submitter;
+        if let Err(error) = repositories::submissions::PartialSubmissionHistory(submission)
+            .fail(&self.db)
+            .await
+        {
+            error!(
+                ?error,
+                ?id,
+                "Error marking interrupted submission as failed"
+            );
+            return;
+        }
+        self.websocket
+            .send_or_queue(submitter, WebSocketSend::TestsError { id });
+    }
+
     pub fn init_hooks(&mut self) -> JoinSet<()> {
         #[allow(unused_mut)] // when no-default-features, we don't mutate this
         let mut jset = JoinSet::<()>::new();
@@ -69,7 +314,8 @@ impl AppState {
             let (mut hook_handler, hooks_tx) =
                 crate::server::hooks::handlers::EventHookHandler::create();
             self.dispatchers.push(hooks_tx);
-            jset.spawn(async move { hook_handler.start().await });
+            let shutdown = self.hooks_shutdown.clone();
+            jset.spawn(async move { hook_handler.start(shutdown).await });
         }
 
         #[cfg(feature = "webhooks")]
@@ -77,7 +323,8 @@ impl AppState {
             let (mut webhook_handler, webhooks_tx) =
                 crate::server::hooks::webhooks::EventWebhookHandler::create();
             self.dispatchers.push(webhooks_tx);
-            jset.spawn(async move { webhook_handler.start().await });
+            let shutdown = self.hooks_shutdown.clone();
+            jset.spawn(async move { webhook_handler.start(shutdown).await });
         }
 
         jset
@@ -89,6 +336,17 @@ impl AppState {
         self.team_manager
             .insert_many(users.into_iter().map(|u| u.id));
 
+        // restore the clock from the last run, so that a restart doesn't reset the time left
+        let clock = self.clock.get_mut();
+        match repositories::clock::get_clock(&self.db).await? {
+            Some(saved) => *clock = saved,
+            None => repositories::clock::create_clock(&self.db, clock).await?,
+        }
+
+        for index in repositories::problems::get_released_problems(&self.db).await? {
+            self.released_problems.insert(index);
+        }
+
         Ok(())
     }
 }
@@ -142,6 +400,8 @@ define_router! {
     clock,
     competition,
     questions,
+    rejudge,
+    resolver,
     teams,
     testing,
     leaderboard,
@@ -0,0 +1,229 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+use crate::{
+    repositories::{
+        self,
+        submissions::SubmissionId,
+        users::{QuestionState, UserId},
+    },
+    server::{
+        clock::ClockInfo,
+        hooks::events::ServerEvent,
+        standings::{self, Standing},
+        AppState,
+    },
+    services::ws::Broadcast,
+    utils,
+};
+
+/// A result that was hidden by the scoreboard freeze and has been revealed by the resolver
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Reveal {
+    pub submission: SubmissionId,
+    pub team: UserId,
+    pub question_index: usize,
+    pub success: bool,
+    /// Rank of the team before this result was revealed
+    pub previous_rank: usize,
+    pub new_rank: usize,
+    pub new_score: f64,
+    pub new_states: Vec<QuestionState>,
+    /// Number of results which are still hidden
+    pub remaining: usize,
+}
+
+/// Number of results which are hidden by the scoreboard freeze, or `None` if the scoreboard is not
+/// frozen
+pub async fn hidden(state: &AppState) -> anyhow::Result<Option<usize>> {
+    let Some(cutoff) = state.clock.read().await.freeze_cutoff() else {
+        return Ok(None);
+    };
+    let hidden = repositories::submissions::get_hidden_submissions(&state.db, cutoff).await?;
+    Ok(Some(hidden.len()))
+}
+
+/// Number of submissions made while the scoreboard was frozen which are still being judged.  Their
+/// results cannot be revealed until they have been judged.
+pub async fn judging(state: &AppState) -> anyhow::Result<usize> {
+    let Some(cutoff) = state.clock.read().await.freeze_cutoff() else {
+        return Ok(0);
+    };
+    let judging = repositories::submissions::count_judging_submissions(&state.db, cutoff).await?;
+    Ok(judging as usize)
+}
+
+/// Reveal the next result hidden by the scoreboard freeze.
+///
+/// Like the ICPC resolver, results are revealed for the team lowest in the standings first, one
+/// submission at a time in the order in which they were made.  Once the last result has been
+/// revealed, the scoreboard is unfrozen and [`ServerEvent::OnResolved`] is dispatched with the final
+/// standings.  Returns `None` if nothing is hidden.
+pub async fn step(state: &Arc<AppState>) -> anyhow::Result<Option<Reveal>> {
+    // held throughout so that concurrent steps cannot reveal the same result
+    let mut clock = state.clock.write().await;
+    let Some(cutoff) = clock.freeze_cutoff() else {
+        return Ok(None);
+    };
+
+    let hidden = repositories::submissions::get_hidden_submissions(&state.db, cutoff).await?;
+    let game = &state.config.game;
+    let before = standings::get_standings(&state.db, game, Some(cutoff)).await?;
+
+    let next = before
+        .iter()
+        .rev()
+        .find_map(|standing| hidden.iter().find(|s| s.submitter == standing.id))
+        // submissions by anybody other than competitors do not affect the standings
+        .or(hidden.first());
+    let Some(next) = next else {
+        unfreeze(state, &mut clock).await?;
+        return Ok(None);
+    };
+
+    repositories::submissions::reveal_submission(&state.db, &next.id).await?;
+
+    let after = standings::get_standings(&state.db, game, Some(cutoff)).await?;
+    let rank = |standings: &[Standing]| {
+        standings
+            .iter()
+            .find(|s| s.id == next.submitter)
+            .map(|s| s.rank)
+            .unwrap_or_default()
+    };
+    let progress = standings::get_progress(
+        &state.db,
+        game,
+        state.config.packet.problems.len(),
+        &next.submitter,
+        Some(cutoff),
+    )
+    .await?;
+
+    let reveal = Reveal {
+        submission: next.id,
+        team: next.submitter,
+        question_index: next.question_index as usize,
+        success: next.success,
+        previous_rank: rank(&before),
+        new_rank: rank(&after),
+        new_score: progress.score,
+        new_states: progress.states,
+        remaining: hidden.len() - 1,
+    };
+    state
+        .websocket
+        .broadcast(Broadcast::ResultRevealed(reveal.clone()));
+
+    if reveal.remaining == 0 {
+        unfreeze(state, &mut clock).await?;
+    }
+
+    Ok(Some(reveal))
+}
+
+async fn unfreeze(state: &Arc<AppState>, clock: &mut ClockInfo) -> anyhow::Result<()> {
+    clock.frozen_at = None;
+    repositories::clock::record_freeze(&state.db, None).await?;
+    info!("All results have been revealed, unfreezing the scoreboard");
+    state.websocket.broadcast(Broadcast::ScoreboardUnfrozen);
+
+    let standings = standings::get_standings(&state.db, &state.config.game, None).await?;
+    if let Err(err) = (ServerEvent::OnResolved {
+        standings,
+        time: utils::utc_now(),
+    }
+    .dispatch(Arc::clone(state)))
+    {
+        error!("Failed to dispatch resolved event: {:?}", err);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use crate::{
+        mock_state,
+        repositories::users::Role,
+        testing::{db_user, submissions_repositories::dummy_submission},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn submissions_being_judged_are_not_revealed() {
+        let state = mock_state! {};
+        let team = db_user(&state.db, "team", Role::Competitor).await;
+        state.clock.write().await.frozen_at = Some(Utc::now() - TimeDelta::hours(1));
+
+        repositories::submissions::create_submission_history(
+            &state.db,
+            repositories::submissions::NewSubmissionHistory {
+                id: SubmissionId::new(),
+                submitter: team.id,
+                code: "",
+                question_index: 0,
+                compile_result: None,
+                language: "java",
+                test_only: false,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(hidden(&state).await.unwrap(), Some(0));
+        assert_eq!(judging(&state).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn results_are_revealed_lowest_team_first() {
+        let mut state = mock_state! {};
+        let (tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+        Arc::get_mut(&mut state).unwrap().dispatchers.push(tx);
+        let leader = db_user(&state.db, "leader", Role::Competitor).await;
+        let trailer = db_user(&state.db, "trailer", Role::Competitor).await;
+        state.clock.write().await.frozen_at = Some(Utc::now() - TimeDelta::hours(1));
+        assert_eq!(hidden(&state).await.unwrap(), Some(0));
+
+        // both teams' submissions are made after the freeze, but only the leader's are revealed
+        dummy_submission(&state.db, &leader, 10., 0).await;
+        repositories::submissions::reveal_submission(
+            &state.db,
+            &dummy_submission(&state.db, &leader, 10., 1).await.id,
+        )
+        .await
+        .unwrap();
+        let hidden_submission = dummy_submission(&state.db, &trailer, 30., 0).await;
+        assert_eq!(hidden(&state).await.unwrap(), Some(2));
+
+        let reveal = step(&state).await.unwrap().unwrap();
+        assert_eq!(reveal.submission, hidden_submission.id);
+        assert_eq!(reveal.previous_rank, 2);
+        assert_eq!(reveal.new_rank, 1);
+        assert_eq!(reveal.new_score, 30.);
+        assert_eq!(reveal.remaining, 1);
+
+        let reveal = step(&state).await.unwrap().unwrap();
+        assert_eq!(reveal.team, leader.id);
+        assert_eq!(reveal.new_score, 20.);
+        assert_eq!(reveal.remaining, 0);
+
+        // the final standings are only published once everything has been revealed
+        let (event, _) = events.try_recv().unwrap();
+        let ServerEvent::OnResolved { standings, .. } = event else {
+            panic!("expected resolved event, got {:?}", event);
+        };
+        assert_eq!(standings[0].id, trailer.id);
+        assert_eq!(standings[0].score, 30.);
+
+        assert!(state.clock.read().await.frozen_at.is_none());
+        assert_eq!(judging(&state).await.unwrap(), 0);
+        assert_eq!(hidden(&state).await.unwrap(), None);
+        assert!(step(&state).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    borrow::Cow,
+    os::unix::process::ExitStatusExt,
+    path::Path,
+    process::{ExitStatus, Stdio},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use bedrock::{language::Language, packet::Test};
+use leucite::{CommandExt, Rules};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    process::{Child, Command},
+};
+
+use crate::{
+    repositories::submissions::{
+        CompileOutput, CompileResultState, NewTestResults, TestResultState,
+    },
+    server::{
+        limits::{Cgroup, Limits},
+        sandbox::{system_rules, WorkDir},
+    },
+};
+
+/// A submission which has been compiled and can be run against tests
+pub struct Compiled {
+    run_command: String,
+    limits: Limits,
+    compile_output: Option<CompileOutput<'static>>,
+    dir: WorkDir,
+}
+
+/// Compile `code` under `compile_limits`, taking at most `compile_timeout`, so that it can be run
+/// under `run_limits`.  If the language has no build step, the code is only written out.
+///
+/// Returns the output of the compiler if compilation failed.
+pub async fn compile(
+    language: &Language,
+    code: &str,
+    compile_limits: Limits,
+    compile_timeout: Duration,
+    run_limits: Limits,
+) -> anyhow::Result<Result<Compiled, CompileOutput<'static>>> {
+    let dir = WorkDir::new("submission").await?;
+    tokio::fs::write(dir.path().join(language.source_file()), code)
+        .await
+        .context("Writing source file")?;
+
+    let compile_output = match language.build_command() {
+        Some(build_command) => {
+            let rules = system_rules()
+                .add_read_write("/tmp")
+                .add_read_write(dir.path());
+            let cgroup = compile_limits.cgroup()?;
+            let child = spawn(
+                &compile_limits.wrap(build_command),
+                dir.path(),
+                rules,
+                cgroup.as_ref(),
+                Stdio::null(),
+            )
+            .context("Spawning compile command")?;
+
+            let output = match tokio::time::timeout(compile_timeout, child.wait_with_output()).await
+            {
+                Ok(output) => {
+                    let output = output.context("Waiting for compile command")?;
+                    CompileOutput {
+                        state: if output.status.success() {
+                            CompileResultState::Success
+                        } else {
+                            CompileResultState::RuntimeFail
+                        },
+                        stdout: Cow::Owned(String::from_utf8_lossy(&output.stdout).into_owned()),
+                        stderr: Cow::Owned(String::from_utf8_lossy(&output.stderr).into_owned()),
+                        exit_status: exit_code(output.status),
+                    }
+                }
+                Err(_) => CompileOutput {
+                    state: CompileResultState::TimedOut,
+                    stdout: Cow::Borrowed(""),
+                    stderr: Cow::Borrowed(""),
+                    exit_status: -1,
+                },
+            };
+            if output.state != CompileResultState::Success {
+                return Ok(Err(output));
+            }
+            Some(output)
+        }
+        None => None,
+    };
+
+    Ok(Ok(Compiled {
+        run_command: language.run_command().to_string(),
+        limits: run_limits,
+        compile_output,
+        dir,
+    }))
+}
+
+impl Compiled {
+    /// Output of the compiler, if the language has a build step
+    pub fn compile_output(&self) -> Option<CompileOutput<'static>> {
+        self.compile_output.clone()
+    }
+
+    /// Limits under which the submission is run
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Start the submission in its sandbox, in `cgroup` if tests are run in cgroups.  If
+    /// `limit_output` is set, the output is cut off just after the limit.
+    pub fn spawn(&self, cgroup: Option<&Cgroup>, limit_output: bool) -> anyhow::Result<Child> {
+        let command = if limit_output {
+            self.limits.wrap_output(&self.run_command)
+        } else {
+            self.limits.wrap(&self.run_command)
+        };
+        let rules = system_rules().add_read_only(self.dir.path());
+        spawn(&command, self.dir.path(), rules, cgroup, Stdio::piped()).context("Spawning solution")
+    }
+
+    /// Run the submission against a single test, comparing its output with the expected output
+    /// exactly, after trimming both if `trim_output` is set
+    pub async fn run(
+        &self,
+        test: &Test,
+        timeout: Duration,
+        trim_output: bool,
+    ) -> anyhow::Result<NewTestResults<'static>> {
+        let cgroup = self.limits.cgroup()?;
+        let mut solution = self.spawn(cgroup.as_ref(), true)?;
+        let start = Instant::now();
+
+        let (Some(mut solution_in), Some(solution_out), Some(solution_err)) = (
+            solution.stdin.take(),
+            solution.stdout.take(),
+            solution.stderr.take(),
+        ) else {
+            anyhow::bail!("Solution is missing a standard stream");
+        };
+
+        // written separately, since the solution may not read its input before writing output
+        let input = test.input.clone();
+        let writer = tokio::spawn(async move {
+            // fails if the solution exits without reading all of its input, which is up to it
+            let _ = solution_in.write_all(input.as_bytes()).await;
+        });
+        let output_bytes = self.limits.output_bytes();
+        let mut stdout = tokio::spawn(relay(solution_out, tokio::io::sink(), output_bytes));
+        let mut stderr = tokio::spawn(relay(solution_err, tokio::io::sink(), output_bytes));
+
+        let execution = async {
+            let status = solution.wait().await;
+            let time_taken = start.elapsed();
+            let stdout = (&mut stdout).await?;
+            let stderr = (&mut stderr).await?;
+            anyhow::Ok((status, time_taken, stdout, stderr))
+        };
+
+        let outcome = tokio::time::timeout(timeout, execution).await;
+        writer.abort();
+        let Ok(outcome) = outcome else {
+            // the solution is killed when it is dropped, and anything it left behind once its
+            // cgroup is
+            stdout.abort();
+            stderr.abort();
+            return Ok(timed_out(timeout));
+        };
+        let (status, time_taken, stdout, stderr) = outcome?;
+        let status = status.context("Waiting for solution")?;
+        let stdout = stdout?;
+        let stderr = String::from_utf8_lossy(&stderr?).into_owned();
+
+        let actual = String::from_utf8_lossy(&stdout);
+        let matches = if trim_output {
+            actual.trim() == test.output.trim()
+        } else {
+            actual == test.output
+        };
+        let state = if !status.success() {
+            TestResultState::RuntimeFail
+        } else if matches {
+            TestResultState::Pass
+        } else {
+            TestResultState::IncorrectOutput
+        };
+
+        Ok(NewTestResults {
+            result: self.limits.classify(
+                state,
+                stdout.len(),
+                exit_code(status),
+                cgroup.as_ref().is_some_and(Cgroup::out_of_memory),
+            ),
+            stdout: Cow::Owned(actual.into_owned()),
+            stderr: Cow::Owned(stderr),
+            exit_status: exit_code(status),
+            time_taken: time_taken.into(),
+        })
+    }
+}
+
+/// Run `command` with `bash -c` from `dir` in a sandbox with `rules`, in `cgroup` if there is
+/// one.  The cgroup is joined before the sandbox is applied, so the sandbox never needs access to
+/// any cgroup.
+fn spawn(
+    command: &str,
+    dir: &Path,
+    rules: Rules,
+    cgroup: Option<&Cgroup>,
+    stdin: Stdio,
+) -> anyhow::Result<Child> {
+    let mut bash = Command::new("bash");
+    bash.arg("-c")
+        .arg(command)
+        .current_dir(dir)
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(cgroup) = cgroup {
+        cgroup.join(&mut bash)?;
+    }
+    Ok(bash.restrict(Arc::new(rules)).spawn()?)
+}
+
+/// Result of a test which took longer than `timeout`
+pub fn timed_out(timeout: Duration) -> NewTestResults<'static> {
+    NewTestResults {
+        result: TestResultState::TimedOut,
+        stdout: Cow::Borrowed(""),
+        stderr: Cow::Borrowed(""),
+        exit_status: -1,
+        time_taken: timeout.into(),
+    }
+}
+
+/// Copy everything from `from` to `to`, returning what was copied.  Reading continues after `to`
+/// has been closed, so that everything that was written is recorded.  Recording stops once more
+/// than `limit` bytes have been copied.
+pub async fn relay(
+    mut from: impl AsyncRead + Unpin,
+    to: impl AsyncWrite + Unpin,
+    limit: usize,
+) -> std::io::Result<Vec<u8>> {
+    let mut to = Some(to);
+    let mut copied = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        let n = from.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(copied);
+        }
+        if copied.len() <= limit {
+            copied.extend_from_slice(&buf[..n]);
+        }
+        if let Some(writer) = &mut to {
+            if writer.write_all(&buf[..n]).await.is_err() || writer.flush().await.is_err() {
+                to = None;
+            }
+        }
+    }
+}
+
+/// Exit code of a process, following the shell convention for processes killed by a signal
+pub fn exit_code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(-1)
+}
+
+#[cfg(test)]
+mod tests {
+    use bedrock::Config;
+
+    use crate::{settings::Settings, testing::SAMPLE_1};
+
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(exit_code(ExitStatus::from_raw(3 << 8)), 3);
+        assert_eq!(exit_code(ExitStatus::from_raw(9)), 137);
+    }
+
+    #[tokio::test]
+    async fn relay_records_output() {
+        let (mut solution, from) = tokio::io::duplex(64);
+        let (to, mut interactor) = tokio::io::duplex(64);
+        let relayed = tokio::spawn(relay(from, to, usize::MAX));
+
+        solution.write_all(b"guess 5\n").await.unwrap();
+        let mut buf = [0; 8];
+        interactor.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"guess 5\n");
+
+        // the interactor has stopped reading, but the output is still recorded
+        drop(interactor);
+        solution.write_all(b"guess 6\n").await.unwrap();
+        drop(solution);
+        assert_eq!(relayed.await.unwrap().unwrap(), b"guess 5\nguess 6\n");
+    }
+
+    /// Run `code` in python against the first test of the sample packet
+    async fn run(code: &str) -> NewTestResults<'static> {
+        let config = Config::from_str(SAMPLE_1, Some("single.toml")).unwrap();
+        let language = config.languages.get_by_str("python3").unwrap();
+        let limits = Settings::default().limits(&config, 0, Some("python3"));
+
+        let compiled = compile(
+            language,
+            code,
+            Limits::compile(&config),
+            Duration::from_secs(10),
+            Limits::run(&config, &limits),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        compiled
+            .run(
+                &config.packet.problems[0].tests[0],
+                Duration::from_secs(1),
+                config.test_runner.trim_output,
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn solutions_are_run_against_tests() {
+        let passed = run("print(input()[::-1])").await;
+        assert_eq!(passed.result, TestResultState::Pass);
+        assert_eq!(passed.stdout, "olleh\n");
+
+        let wrong = run("print(input())").await;
+        assert_eq!(wrong.result, TestResultState::IncorrectOutput);
+
+        let crashed = run("import sys\nsys.exit(3)").await;
+        assert_eq!(crashed.result, TestResultState::RuntimeFail);
+        assert_eq!(crashed.exit_status, 3);
+
+        let slow = run("import time\ninput()\ntime.sleep(10)").await;
+        assert_eq!(slow.result, TestResultState::TimedOut);
+    }
+}
@@ -0,0 +1,44 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use leucite::Rules;
+use rand::{distributions::Alphanumeric, Rng};
+
+/// A temporary directory in which a program is run or its files are written, which is removed once
+/// it is dropped, including when the program times out
+pub struct WorkDir(PathBuf);
+
+impl WorkDir {
+    /// Create a new directory, whose name starts with `basalt-{kind}`
+    pub async fn new(kind: &str) -> anyhow::Result<Self> {
+        let name: String = rand::thread_rng()
+            .sample_iter(Alphanumeric)
+            .take(12)
+            .map(char::from)
+            .collect();
+        let dir = std::env::temp_dir().join(format!("basalt-{}-{}", kind, name));
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .with_context(|| format!("Creating {} directory", kind))?;
+        Ok(Self(dir))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Sandbox rules which allow reading the parts of the system that programs need to run
+pub fn system_rules() -> Rules {
+    Rules::new()
+        .add_read_only("/usr")
+        .add_read_only("/etc")
+        .add_read_only("/dev")
+        .add_read_only("/bin")
+}
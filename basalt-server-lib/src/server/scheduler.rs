@@ -0,0 +1,307 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{TimeDelta, Utc};
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info};
+
+use crate::{
+    repositories,
+    server::{hooks::events::ServerEvent, standings, AppState},
+    services::{questions, ws::Broadcast},
+};
+
+/// How often the clock is checked
+const TICK: Duration = Duration::from_secs(1);
+/// How often the state of the clock is sent to every connection, even if it has not changed
+const SYNC: Duration = Duration::from_secs(10);
+
+/// Start the competition at its scheduled time, if it has one, and end it once the time limit has
+/// run out.  The clock is also broadcast periodically so that clients do not drift.
+///
+/// This runs until the server shuts down.
+pub async fn run(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(TICK);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut sync = tokio::time::interval(SYNC);
+    sync.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    // the end is only announced once, including across restarts
+    let mut ended = state.is_game_over().await;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if let Err(error) = tick(&state, &mut ended).await {
+                    error!(?error, "Error updating the clock");
+                }
+            }
+            _ = sync.tick() => state.broadcast_clock(&*state.clock.read().await),
+            _ = state.shutdown.cancelled() => break,
+        }
+    }
+}
+
+async fn tick(state: &Arc<AppState>, ended: &mut bool) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let time_limit = state.time_limit();
+    let mut clock = state.clock.write().await;
+
+    if let Some(start) = state.settings.schedule.start {
+        if now >= start && !clock.has_started() {
+            // the clock runs from the scheduled time, even if the server was not running then
+            let start = start.max(clock.start_time);
+            clock.unpause_at(start);
+            repositories::clock::record_unpause(&state.db, start, clock.total_time_paused).await?;
+
+            info!(%start, "Starting the competition as scheduled");
+            state.websocket.broadcast(Broadcast::GameStarted {
+                questions: questions::released_questions(state).await,
+            });
+            state.websocket.broadcast(Broadcast::GameUnpaused {
+                time_left_in_seconds: clock.time_at(now)?.time_left(time_limit).as_secs(),
+            });
+            state.broadcast_clock(&clock);
+        }
+    }
+
+    if clock.has_started() {
+        let elapsed = clock.time_at(now)?.duration;
+        for problem in &state.settings.problems {
+            if problem
+                .release_after()
+                .is_some_and(|after| elapsed >= after && !state.is_released(problem.index))
+            {
+                state.release_problem(problem.index).await?;
+            }
+        }
+    }
+
+    if let Some(freeze) = state.settings.schedule.freeze() {
+        let time_left = clock.time_at(now)?.time_left(time_limit);
+        // once the competition is over, the scoreboard stays as it is until it has been resolved
+        let in_window = clock.has_started() && !time_left.is_zero() && time_left <= freeze;
+        if in_window && clock.frozen_at.is_none() {
+            clock.frozen_at = Some(now);
+            repositories::clock::record_freeze(&state.db, clock.frozen_at).await?;
+            info!("Freezing the scoreboard");
+            state.websocket.broadcast(Broadcast::ScoreboardFrozen);
+        } else if time_left > freeze && clock.frozen_at.is_some() {
+            // time has been added, so the freeze window has not begun after all
+            clock.frozen_at = None;
+            repositories::clock::record_freeze(&state.db, None).await?;
+            info!("Unfreezing the scoreboard");
+            state.websocket.broadcast(Broadcast::ScoreboardUnfrozen);
+        }
+    }
+
+    // the competition is only over once time has run out for the team with the most time
+    let mut current = clock.time_at(now)?;
+    current.time_adjustment += clock.latest_team_offset();
+    let Some(overtime) = current.duration.checked_sub(current.time_limit(time_limit)) else {
+        // time may have been added back since the competition ended
+        *ended = false;
+        return Ok(());
+    };
+
+    if !clock.is_paused() {
+        // stop the clock at the moment that time ran out
+        let end = now - TimeDelta::from_std(overtime)?;
+        clock.pause_at(end);
+        repositories::clock::record_pause(&state.db, end).await?;
+        state.broadcast_clock(&clock);
+    }
+
+    if !*ended {
+        *ended = true;
+        info!("Time is up, no longer accepting submissions");
+        state.websocket.broadcast(Broadcast::GameOver);
+
+        // results hidden by the freeze are only published once the resolver has revealed them
+        let cutoff = clock.freeze_cutoff();
+        let standings = standings::get_standings(&state.db, &state.config.game, cutoff).await?;
+        if let Err(err) = (ServerEvent::OnContestEnd {
+            standings,
+            time: now,
+        }
+        .dispatch(state.clone()))
+        {
+            error!("Failed to dispatch contest end event: {:?}", err);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        mock_state,
+        repositories::users::UserId,
+        server::{clock::ClockInfo, websocket::ConnectionKind},
+        services::ws::WebSocketSend,
+        settings::ProblemSettings,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn scheduled_start() {
+        let mut state = mock_state! {};
+        let start = Utc::now() - TimeDelta::minutes(1);
+        Arc::get_mut(&mut state).unwrap().settings.schedule.start = Some(start);
+
+        let mut rx = state.websocket.add_connection(ConnectionKind::User {
+            user: UserId::new(),
+        });
+
+        let mut ended = false;
+        tick(&state, &mut ended).await.unwrap();
+
+        // the released questions are sent along with the start, so they need not be fetched
+        let started = std::iter::from_fn(|| rx.try_recv().ok())
+            .any(|m| matches!(m, WebSocketSend::Broadcast(Broadcast::GameStarted { .. })));
+        assert!(started);
+
+        let clock = state.clock.read().await;
+        assert!(clock.has_started());
+        assert!(!clock.is_paused());
+        assert!(!ended);
+    }
+
+    #[tokio::test]
+    async fn clock_is_synced_periodically() {
+        let state = mock_state! {};
+        let team = UserId::new();
+        let mut team_rx = state
+            .websocket
+            .add_connection(ConnectionKind::User { user: team });
+        let mut other_rx = state.websocket.add_connection(ConnectionKind::User {
+            user: UserId::new(),
+        });
+        state
+            .clock
+            .write()
+            .await
+            .set_team_offset(team, TimeDelta::minutes(10));
+
+        // nothing is read from the database once the scheduler is running, so time can be skipped
+        tokio::time::pause();
+        let scheduler = tokio::spawn(run(Arc::clone(&state)));
+        tokio::time::sleep(SYNC * 2 + TICK).await;
+        state.shutdown.cancel();
+        scheduler.await.unwrap();
+
+        let synced = |rx: &mut tokio::sync::mpsc::UnboundedReceiver<WebSocketSend>| {
+            std::iter::from_fn(|| rx.try_recv().ok())
+                .filter_map(|message| match message {
+                    WebSocketSend::Broadcast(Broadcast::Clock {
+                        time_left_in_seconds,
+                        ..
+                    }) => Some(time_left_in_seconds),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let time_limit = state.time_limit().as_secs();
+        // once straight away, then once every interval
+        assert_eq!(synced(&mut team_rx), [time_limit + 600; 3]);
+        assert_eq!(synced(&mut other_rx), [time_limit; 3]);
+    }
+
+    #[tokio::test]
+    async fn time_runs_out() {
+        let mut state = mock_state! {};
+        let (tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+        Arc::get_mut(&mut state).unwrap().dispatchers.push(tx);
+        let time_limit = state.time_limit();
+        *state.clock.write().await = ClockInfo {
+            start_time: Utc::now()
+                - TimeDelta::from_std(time_limit).unwrap()
+                - TimeDelta::minutes(5),
+            pause_time: None,
+            total_time_paused: Duration::ZERO,
+            time_adjustment: TimeDelta::zero(),
+            team_offsets: Default::default(),
+            frozen_at: None,
+        };
+        assert!(state.is_game_over().await);
+        let mut rx = state.websocket.add_connection(ConnectionKind::User {
+            user: UserId::new(),
+        });
+
+        let mut ended = false;
+        tick(&state, &mut ended).await.unwrap();
+        assert!(ended);
+
+        // clients are told that their countdown has stopped
+        let mut synced = false;
+        while let Ok(message) = rx.try_recv() {
+            synced |= matches!(
+                message,
+                WebSocketSend::Broadcast(Broadcast::Clock {
+                    is_paused: true,
+                    time_left_in_seconds: 0,
+                    ..
+                })
+            );
+        }
+        assert!(synced);
+
+        let (event, _) = events.try_recv().unwrap();
+        assert!(matches!(event, ServerEvent::OnContestEnd { .. }));
+
+        let clock = state.clock.read().await;
+        assert!(clock.is_paused());
+        // the clock is stopped at the end rather than when the end was noticed
+        assert!(clock.current_time().unwrap().duration - time_limit < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn problems_are_released() {
+        let mut state = mock_state! {};
+        Arc::get_mut(&mut state)
+            .unwrap()
+            .settings
+            .problems
+            .push(ProblemSettings {
+                index: 0,
+                release_after: Some(30),
+                ..Default::default()
+            });
+        assert!(!state.is_released(0));
+
+        let mut ended = false;
+        state.clock.write().await.unpause();
+        tick(&state, &mut ended).await.unwrap();
+        assert!(!state.is_released(0));
+
+        state.clock.write().await.start_time -= TimeDelta::minutes(31);
+        tick(&state, &mut ended).await.unwrap();
+        assert!(state.is_released(0));
+    }
+
+    #[tokio::test]
+    async fn scoreboard_freezes() {
+        let mut state = mock_state! {};
+        Arc::get_mut(&mut state).unwrap().settings.schedule.freeze = Some(60);
+        let time_limit = state.time_limit();
+        let elapsed = time_limit.saturating_sub(Duration::from_secs(30 * 60));
+        *state.clock.write().await = ClockInfo {
+            start_time: Utc::now() - TimeDelta::from_std(elapsed).unwrap(),
+            pause_time: None,
+            total_time_paused: Duration::ZERO,
+            time_adjustment: TimeDelta::zero(),
+            team_offsets: Default::default(),
+            frozen_at: None,
+        };
+
+        let mut ended = false;
+        tick(&state, &mut ended).await.unwrap();
+        assert!(state.clock.read().await.frozen_at.is_some());
+
+        // adding time moves the freeze window back
+        state.clock.write().await.adjust(TimeDelta::hours(1));
+        tick(&state, &mut ended).await.unwrap();
+        assert!(state.clock.read().await.frozen_at.is_none());
+        assert!(!ended);
+    }
+}
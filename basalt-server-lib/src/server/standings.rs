@@ -0,0 +1,281 @@
+use bedrock::Game;
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteExecutor;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::repositories::{
+    self,
+    users::{QuestionState, Role, UserId},
+};
+
+/// Placement of a single team in the competition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Standing {
+    /// 1-based placement of the team.  Teams that are tied share the same rank.
+    pub rank: usize,
+    pub id: UserId,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub score: f64,
+    /// Number of problems for which the latest submission passed all tests.  In race mode, this is
+    /// the number of problems which have ever been solved.
+    pub solved: u32,
+    /// Time at which the team solved the last of its solved problems.  Only set in race mode.
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub last_solve: Option<OffsetDateTime>,
+}
+
+/// Score and problem states of a single team, as shown on the scoreboard
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub score: f64,
+    pub states: Vec<QuestionState>,
+}
+
+/// Get the score of a team, only counting submissions made before `before` if it is set.
+///
+/// In race mode, the score is the number of problems that the team has solved.
+pub async fn get_score(
+    db: impl SqliteExecutor<'_>,
+    game: &Game,
+    user: &UserId,
+    before: Option<OffsetDateTime>,
+) -> anyhow::Result<f64> {
+    match (game, before) {
+        (Game::Points(_), None) => repositories::submissions::get_user_score(db, user).await,
+        (Game::Points(_), Some(before)) => {
+            repositories::submissions::get_user_score_before(db, user, before).await
+        }
+        (Game::Race(_), _) => Ok(get_first_solves(db, user, before).await?.len() as f64),
+    }
+}
+
+/// Get the score of a team and the state of each of the `problems` problems in the packet, only
+/// counting submissions made before `before` if it is set
+pub async fn get_progress(
+    db: impl SqliteExecutor<'_> + Copy,
+    game: &Game,
+    problems: usize,
+    user: &UserId,
+    before: Option<OffsetDateTime>,
+) -> anyhow::Result<Progress> {
+    let mut states = vec![QuestionState::NotAttempted; problems];
+    for s in get_latest_submissions(db, user, before).await? {
+        states[s.question_index as usize] = if s.success {
+            QuestionState::Pass
+        } else {
+            QuestionState::Fail
+        };
+    }
+
+    for c in repositories::submissions::count_tests(db, user).await? {
+        if states[c.question_index as usize] == QuestionState::NotAttempted && c.count > 0 {
+            states[c.question_index as usize] = QuestionState::InProgress;
+        }
+    }
+
+    Ok(Progress {
+        score: get_score(db, game, user, before).await?,
+        states,
+    })
+}
+
+async fn get_latest_submissions(
+    db: impl SqliteExecutor<'_>,
+    user: &UserId,
+    before: Option<OffsetDateTime>,
+) -> anyhow::Result<Vec<repositories::submissions::SubmissionHistory>> {
+    match before {
+        Some(before) => {
+            repositories::submissions::get_latest_submissions_before(db, user, before).await
+        }
+        None => repositories::submissions::get_latest_submissions(db, user).await,
+    }
+}
+
+async fn get_first_solves(
+    db: impl SqliteExecutor<'_>,
+    user: &UserId,
+    before: Option<OffsetDateTime>,
+) -> anyhow::Result<Vec<repositories::submissions::Solve>> {
+    match before {
+        Some(before) => repositories::submissions::get_first_solves_before(db, user, before).await,
+        None => repositories::submissions::get_first_solves(db, user).await,
+    }
+}
+
+/// Compute the standings of all competitors, ordered by placement.  If `before` is set, only
+/// submissions made before then are counted.
+///
+/// In points mode teams are ordered by score.  In race mode they are ordered by the number of
+/// problems solved, then by who got there first.
+pub async fn get_standings(
+    db: impl SqliteExecutor<'_> + Copy,
+    game: &Game,
+    before: Option<OffsetDateTime>,
+) -> anyhow::Result<Vec<Standing>> {
+    let competitors = repositories::users::get_users_with_role(db, Role::Competitor).await?;
+
+    let mut standings = Vec::with_capacity(competitors.len());
+    for user in competitors {
+        let (score, solved, last_solve) = match game {
+            Game::Points(_) => {
+                let score = get_score(db, game, &user.id, before).await?;
+                let solved = get_latest_submissions(db, &user.id, before)
+                    .await?
+                    .iter()
+                    .filter(|s| s.success)
+                    .count() as u32;
+                (score, solved, None)
+            }
+            Game::Race(_) => {
+                let solves = get_first_solves(db, &user.id, before).await?;
+                let last_solve = solves.iter().map(|s| s.time).max();
+                (solves.len() as f64, solves.len() as u32, last_solve)
+            }
+        };
+
+        standings.push(Standing {
+            rank: 0,
+            id: user.id,
+            username: user.username,
+            display_name: user.display_name,
+            score,
+            solved,
+            last_solve,
+        });
+    }
+
+    match game {
+        Game::Points(_) => standings.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| b.solved.cmp(&a.solved))
+        }),
+        Game::Race(_) => sort_race(&mut standings),
+    }
+    assign_ranks(&mut standings);
+
+    Ok(standings)
+}
+
+/// Order teams by the number of problems solved, breaking ties by who solved them first
+fn sort_race(standings: &mut [Standing]) {
+    // teams without any solves have no `last_solve`, but they are already sorted last
+    standings.sort_by(|a, b| {
+        b.solved
+            .cmp(&a.solved)
+            .then_with(|| a.last_solve.cmp(&b.last_solve))
+    });
+}
+
+/// Assign ranks to already sorted standings, giving tied teams the same rank (1, 1, 3, ...)
+fn assign_ranks(standings: &mut [Standing]) {
+    for i in 0..standings.len() {
+        standings[i].rank = if i > 0
+            && standings[i].score == standings[i - 1].score
+            && standings[i].solved == standings[i - 1].solved
+            && standings[i].last_solve == standings[i - 1].last_solve
+        {
+            standings[i - 1].rank
+        } else {
+            i + 1
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{mock_db, submissions_repositories::dummy_submission, users_repositories};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn standings_are_ranked() {
+        let db = mock_db().await;
+        let first = users_repositories::dummy_user(&db, "first", "", Role::Competitor).await;
+        let tied_a = users_repositories::dummy_user(&db, "tied_a", "", Role::Competitor).await;
+        let tied_b = users_repositories::dummy_user(&db, "tied_b", "", Role::Competitor).await;
+        let last = users_repositories::dummy_user(&db, "last", "", Role::Competitor).await;
+        users_repositories::dummy_user(&db, "host", "", Role::Host).await;
+
+        dummy_submission(&db, &first, 10., 0).await;
+        dummy_submission(&db, &first, 10., 1).await;
+        dummy_submission(&db, &tied_a, 10., 0).await;
+        dummy_submission(&db, &tied_b, 10., 1).await;
+
+        let standings = get_standings(&db, &bedrock::Config::default().game, None)
+            .await
+            .unwrap();
+        let ranks: Vec<_> = standings.iter().map(|s| (s.id, s.rank)).collect();
+
+        assert_eq!(standings.len(), 4);
+        assert_eq!(ranks[0], (first.id, 1));
+        assert!(ranks[1..3].contains(&(tied_a.id, 2)));
+        assert!(ranks[1..3].contains(&(tied_b.id, 2)));
+        assert_eq!(ranks[3], (last.id, 4));
+        assert_eq!(standings[0].solved, 2);
+    }
+
+    #[tokio::test]
+    async fn frozen_progress() {
+        let db = mock_db().await;
+        let team = users_repositories::dummy_user(&db, "team", "", Role::Competitor).await;
+        dummy_submission(&db, &team, 10., 1).await;
+        let game = bedrock::Config::default().game;
+
+        let live = get_progress(&db, &game, 2, &team.id, None).await.unwrap();
+        assert_eq!(live.score, 10.);
+        assert_eq!(
+            live.states,
+            vec![QuestionState::NotAttempted, QuestionState::Pass]
+        );
+
+        let frozen_at = OffsetDateTime::now_utc() - time::Duration::hours(1);
+        let frozen = get_progress(&db, &game, 2, &team.id, Some(frozen_at))
+            .await
+            .unwrap();
+        assert_eq!(frozen.score, 0.);
+        assert_eq!(frozen.states, vec![QuestionState::NotAttempted; 2]);
+
+        let standings = get_standings(&db, &game, Some(frozen_at)).await.unwrap();
+        assert_eq!(standings[0].solved, 0);
+    }
+
+    #[test]
+    fn race_ordering() {
+        let start = OffsetDateTime::now_utc();
+        let standing = |username: &str, solved: u32, minutes: Option<i64>| Standing {
+            rank: 0,
+            id: UserId::new(),
+            username: username.into(),
+            display_name: None,
+            score: solved as f64,
+            solved,
+            last_solve: minutes.map(|m| start + time::Duration::minutes(m)),
+        };
+        let mut standings = vec![
+            standing("none", 0, None),
+            standing("slow", 2, Some(30)),
+            standing("fewer", 1, Some(5)),
+            standing("fast", 2, Some(20)),
+            standing("tied", 2, Some(30)),
+        ];
+
+        sort_race(&mut standings);
+        assign_ranks(&mut standings);
+
+        let ranks: Vec<_> = standings
+            .iter()
+            .map(|s| (s.username.as_str(), s.rank))
+            .collect();
+        assert_eq!(ranks[0], ("fast", 1));
+        assert!(ranks[1..3].contains(&("slow", 2)));
+        assert!(ranks[1..3].contains(&("tied", 2)));
+        assert_eq!(ranks[3], ("fewer", 4));
+        assert_eq!(ranks[4], ("none", 5));
+    }
+}
@@ -1,121 +1,97 @@
 use std::{
-    collections::HashMap,
-    path::{Path, PathBuf},
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
     sync::Arc,
     time::{Duration, Instant},
 };
 
-use bedrock::{packet::Test, scoring::Scorable, Config};
+use anyhow::Context;
+use bedrock::{language::Language, packet::Test, scoring::Scorable, Config};
 use dashmap::DashMap;
-use erudite::{
-    error::CompileError,
-    runner::{TestResult, TestResultState, TestRunner},
-    BorrowedFileContent, Rules, TestContext,
-};
-use serde::Serialize;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteExecutor;
+use time::OffsetDateTime;
 use tokio::sync::oneshot;
-use tracing::{debug, error};
+use tokio_util::task::TaskTracker;
+use tracing::{debug, error, warn};
 use utoipa::ToSchema;
 
 use crate::{
     repositories::{
         self,
-        submissions::SubmissionId,
-        users::{QuestionState, UserId},
+        submissions::{
+            CompileOutput, NewTestResults, PartialSubmissionHistory, SubmissionHistory,
+            SubmissionId, SubmissionState, TestResultState as DbTestResultState,
+        },
+        users::{Role, UserId},
+    },
+    server::{
+        checker::Checker,
+        interactive::Interactor,
+        limits::Limits,
+        runner::{self, Compiled},
+        standings,
+        websocket::ConnectionKind,
+        AppState, ServerEvent,
     },
-    server::{AppState, ServerEvent},
     services::ws::{Broadcast, Results, TeamUpdate, WebSocketSend},
+    settings::Settings,
     utils,
 };
 
-#[derive(Debug, Copy, Clone, Default)]
-pub struct TestData {
-    pub visible: bool,
-}
-
-impl From<&Test> for TestData {
-    fn from(value: &Test) -> Self {
-        Self {
-            visible: value.visible,
-        }
-    }
-}
-
-pub struct ContextExtended {
-    context: Arc<TestContext<usize, TestData>>,
-    dest: PathBuf,
-}
-
 pub struct Tester {
-    // language raw name : test context
-    contexts: HashMap<String, ContextExtended>,
+    // (language raw name, problem index) : limits under which submissions are run, and how long
+    // each test may take
+    run_limits: HashMap<(String, usize), (Limits, Duration)>,
     abort_handles: DashMap<SubmissionId, oneshot::Sender<()>>,
+    /// Tasks which are judging submissions, so that they can be drained on shutdown
+    tasks: TaskTracker,
+    compile_limits: Limits,
+    /// How long submissions may take to compile, from `timeout` in the `[test_runner]` section of
+    /// the configuration
+    compile_timeout: Duration,
+    /// Whether output is trimmed before it is compared with the expected output, from
+    /// `trim_output` in the `[test_runner]` section of the configuration
+    trim_output: bool,
 }
 
 impl Tester {
-    pub fn new(config: &Config) -> Self {
-        let start = Instant::now();
-        let contexts: HashMap<_, _> = config
+    /// Create a tester with the limits of every problem in every language that may be used for
+    /// it, so that each problem is run with its own limits
+    pub fn new(config: &Config, settings: &Settings) -> Self {
+        let run_limits = config
             .languages
             .iter()
-            .map(|l| {
-                let compile_rules = Rules::new()
-                    .add_read_write("/tmp")
-                    .add_read_only("/usr")
-                    .add_read_only("/etc")
-                    .add_read_only("/dev")
-                    .add_read_only("/bin");
-                let run_rules = Rules::new()
-                    .add_read_only("/usr")
-                    .add_read_only("/etc")
-                    .add_read_only("/dev")
-                    .add_read_only("/bin");
-
-                let groups = config
+            .flat_map(|l| {
+                config
                     .packet
                     .problems
                     .iter()
                     .enumerate()
-                    .filter(|(_, p)| {
+                    .filter(move |(_, p)| {
                         p.languages
                             .as_ref()
                             .is_none_or(|pl| pl.contains(l.raw_name()))
                     })
-                    .map(move |(i, p)| {
-                        (
-                            i,
-                            p.tests
-                                .iter()
-                                .map(|t| (&t.input, &*t.output, TestData::from(t))),
-                        )
-                    });
-
-                let mut c = TestContext::builder()
-                    .run_command(["bash", "-c", l.run_command()])
-                    .run_timeout(config.test_runner.timeout)
-                    .trim_output(config.test_runner.trim_output)
-                    .test_groups(groups)
-                    .compile_rules(compile_rules)
-                    .run_rules(run_rules);
-
-                if let Some(compile_command) = l.build_command() {
-                    c = c.compile_command(["bash", "-c", compile_command])
-                };
-
+                    .map(move |(i, _)| (l, i))
+            })
+            .map(|(l, i)| {
+                let limits = settings.limits(config, i, Some(l.raw_name()));
                 (
-                    l.raw_name().to_string(),
-                    ContextExtended {
-                        context: Arc::new(c.build()),
-                        dest: PathBuf::from(l.source_file()),
-                    },
+                    (l.raw_name().to_string(), i),
+                    (Limits::run(config, &limits), limits.time),
                 )
             })
             .collect();
-        debug!(in = ?start.elapsed(), count = contexts.len(), "Generated all test contexts");
 
         Self {
-            contexts,
+            run_limits,
             abort_handles: Default::default(),
+            tasks: TaskTracker::new(),
+            compile_limits: Limits::compile(config),
+            compile_timeout: config.test_runner.timeout,
+            trim_output: config.test_runner.trim_output,
         }
     }
 
@@ -130,16 +106,192 @@ impl Tester {
             .is_some_and(|(_, x)| x.send(()).is_ok())
     }
 
-    pub fn runner(
+    /// Abort all tests which are still running, returning the IDs of those that were aborted
+    pub fn abort_all(&self) -> Vec<SubmissionId> {
+        let ids: Vec<_> = self.abort_handles.iter().map(|e| *e.key()).collect();
+        ids.into_iter().filter(|&id| self.abort(id)).collect()
+    }
+
+    /// Stop accepting new tests and wait up to `timeout` for the running ones to finish.
+    ///
+    /// Returns whether all tests finished in time.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.tasks.close();
+        tokio::time::timeout(timeout, self.tasks.wait())
+            .await
+            .is_ok()
+    }
+
+    /// Limits under which submissions in `language` are run against the tests of the problem at
+    /// `problem_index`, and how long each test may take.  `None` if the language may not be used
+    /// for the problem.
+    pub fn run_limits(&self, language: &str, problem_index: usize) -> Option<(Limits, Duration)> {
+        self.run_limits
+            .get(&(language.to_string(), problem_index))
+            .copied()
+    }
+
+    /// Compile `code` in `language` so that it can be run under `run_limits`
+    async fn compile(
         &self,
+        language: &Language,
+        code: &str,
+        run_limits: Limits,
+    ) -> anyhow::Result<Result<Compiled, CompileOutput<'static>>> {
+        runner::compile(
+            language,
+            code,
+            self.compile_limits,
+            self.compile_timeout,
+            run_limits,
+        )
+        .await
+    }
+
+    /// Run a compiled submission against `tests` concurrently, yielding the result of each test
+    /// as it finishes.  Submissions to interactive problems are judged by the `interactor`,
+    /// otherwise output is judged by the `checker`.
+    fn run_tests<'a>(
+        &'a self,
+        compiled: &'a Compiled,
+        interactor: Option<&'a Interactor>,
+        checker: &'a Checker,
+        tests: impl Iterator<Item = (usize, &'a Test)> + 'a,
+        timeout: Duration,
+    ) -> impl Stream<Item = (usize, anyhow::Result<NewTestResults<'static>>)> + 'a {
+        let parallelism = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        futures::stream::iter(tests)
+            .map(move |(index, test)| async move {
+                let result = match interactor {
+                    Some(interactor) => interactor.run(compiled, test, timeout).await,
+                    None => {
+                        async {
+                            let mut result = compiled.run(test, timeout, self.trim_output).await?;
+                            result.result = check_result(checker, test, &result, timeout).await;
+                            anyhow::Ok(result)
+                        }
+                        .await
+                    }
+                };
+                (index, result)
+            })
+            .buffer_unordered(parallelism)
+    }
+}
+
+/// Decide the state of a test using the problem's checker.  Output is compared exactly when the
+/// test is run, so only tests which ran to completion are checked again, and only if the checker
+/// is more lenient than that.
+async fn check_result(
+    checker: &Checker,
+    test: &Test,
+    result: &NewTestResults<'_>,
+    timeout: Duration,
+) -> DbTestResultState {
+    let ran = matches!(
+        result.result,
+        DbTestResultState::Pass | DbTestResultState::IncorrectOutput
+    );
+    if checker.is_exact() || !ran {
+        return result.result;
+    }
+
+    match checker
+        .accepts(&test.input, &test.output, &result.stdout, timeout)
+        .await
+    {
+        Ok(true) => DbTestResultState::Pass,
+        Ok(false) => DbTestResultState::IncorrectOutput,
+        Err(error) => {
+            error!(?error, "Error checking output, treating it as incorrect");
+            DbTestResultState::IncorrectOutput
+        }
+    }
+}
+
+/// A test case which a reference solution did not pass
+#[derive(Debug, Clone)]
+pub struct FailedTest {
+    pub index: usize,
+    pub state: repositories::submissions::TestResultState,
+    pub expected: String,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Outcome of running a reference solution through the test runner
+#[derive(Debug, Clone)]
+pub enum SolutionCheck {
+    /// The language is not available for this problem
+    MissingRunner,
+    CompileFailed {
+        stdout: String,
+        stderr: String,
+    },
+    Ran {
+        passed: u32,
+        failures: Vec<FailedTest>,
+    },
+}
+
+impl Tester {
+    /// Run `code` against every test of a problem, reporting any test that does not pass.
+    ///
+    /// This uses the same pipeline as submissions, so it can be used to check that a problem's
+    /// expected outputs agree with a known-good solution.
+    pub async fn check_solution(
+        &self,
+        config: &Config,
+        checker: &Checker,
+        interactor: Option<&Interactor>,
         language: &str,
         problem_index: usize,
-    ) -> Option<(TestRunner<'_, usize, TestData>, &Path)> {
-        let ctx = self.contexts.get(language)?;
-        Some((
-            Arc::clone(&ctx.context).test_runner(&problem_index)?,
-            &ctx.dest,
-        ))
+        code: &str,
+    ) -> anyhow::Result<SolutionCheck> {
+        let Some((run_limits, timeout)) = self.run_limits(language, problem_index) else {
+            return Ok(SolutionCheck::MissingRunner);
+        };
+        let language = config
+            .languages
+            .get_by_str(language)
+            .context("Language has limits but does not exist")?;
+        let compiled = match self.compile(language, code, run_limits).await? {
+            Ok(compiled) => compiled,
+            Err(output) => {
+                return Ok(SolutionCheck::CompileFailed {
+                    stdout: output.stdout.into_owned(),
+                    stderr: output.stderr.into_owned(),
+                });
+            }
+        };
+
+        let tests = &config.packet.problems[problem_index].tests;
+        let mut passed = 0;
+        let mut failures = Vec::new();
+        let mut results = self.run_tests(
+            &compiled,
+            interactor,
+            checker,
+            tests.iter().enumerate(),
+            timeout,
+        );
+        while let Some((index, result)) = results.next().await {
+            let result = result.context("Running test")?;
+            if result.result == DbTestResultState::Pass {
+                passed += 1;
+            } else {
+                failures.push(FailedTest {
+                    index,
+                    state: result.result,
+                    expected: tests[index].output.clone(),
+                    stdout: result.stdout.into_owned(),
+                    stderr: result.stderr.into_owned(),
+                });
+            }
+        }
+        failures.sort_by_key(|f| f.index);
+
+        Ok(SolutionCheck::Ran { passed, failures })
     }
 }
 
@@ -149,7 +301,7 @@ enum TestWsSend {
     Cancelled,
     CompileFailed,
     Compiled { stdout: String, stderr: String },
-    Result(TestResult<TestData>),
+    Result(repositories::submissions::TestResults),
 }
 
 fn spawn_ws_sender(
@@ -247,7 +399,7 @@ fn spawn_ws_sender(
                         websocket_sender.send(WebSocketSend::TestsCompiled { id, stdout, stderr });
                     None
                 }
-                TestWsSend::Result(ref r) => {
+                TestWsSend::Result(r) => {
                     results.push(r);
                     None
                 }
@@ -289,7 +441,7 @@ fn spawn_ws_sender(
                             stderr,
                         });
                     }
-                    TestWsSend::Result(ref r) => results.push(r),
+                    TestWsSend::Result(r) => results.push(r),
                 }
             }
 
@@ -318,49 +470,51 @@ fn spawn_ws_sender(
 }
 
 async fn broadcast_team_update(state: &AppState, user_id: UserId) -> Result<(), ()> {
-    let submissions = repositories::submissions::get_latest_submissions(&state.db, &user_id)
+    let user = repositories::users::get_user_by_id(&state.db, &user_id)
         .await
         .map_err(|error| error!(?error, "Error getting user submissions"))?;
+    let user = &user;
 
-    let mut states = vec![QuestionState::NotAttempted; state.config.packet.problems.len()];
-    for s in submissions {
-        states[s.question_index as usize] = if s.success {
-            QuestionState::Pass
-        } else {
-            QuestionState::Fail
-        }
-    }
-
-    match repositories::submissions::count_tests(&state.db, &user_id).await {
-        Ok(counts) => {
-            for c in counts {
-                if states[c.question_index as usize] == QuestionState::NotAttempted && c.count > 0 {
-                    states[c.question_index as usize] = QuestionState::InProgress;
-                }
-            }
-        }
-        Err(err) => {
-            tracing::error!("Error while getting attempts: {}", err);
-        }
-    }
-
-    let new_score = repositories::submissions::get_user_score(&state.db, &user_id)
+    let update = |before: Option<OffsetDateTime>| async move {
+        let progress = standings::get_progress(
+            &state.db,
+            &state.config.game,
+            state.config.packet.problems.len(),
+            &user_id,
+            before,
+        )
         .await
-        .map_err(|error| error!(?error, "Error getting user score"))?;
+        .map_err(|error| error!(?error, "Error getting team progress"))?;
+        Ok::<_, ()>(Broadcast::TeamUpdate {
+            teams: vec![TeamUpdate {
+                id: user_id,
+                name: user.username.clone(),
+                display_name: user.display_name.clone(),
+                new_score: progress.score,
+                new_states: progress.states,
+            }],
+        })
+    };
 
-    let user = repositories::users::get_user_by_id(&state.db, &user_id)
-        .await
-        .map_err(|error| error!(?error, "Error getting user submissions"))?;
+    let live = update(None).await?;
+    match state.scoreboard_cutoff(None).await {
+        None => state.websocket.broadcast(live),
+        Some(cutoff) => {
+            // while the scoreboard is frozen, only hosts see the update as it happened
+            let hosts = repositories::users::get_users_with_role(&state.db, Role::Host)
+                .await
+                .map_err(|error| error!(?error, "Error getting hosts"))?
+                .into_iter()
+                .map(|u| u.id)
+                .collect::<HashSet<_>>();
+            let is_host = |who: &ConnectionKind| who.user().is_some_and(|u| hosts.contains(u));
 
-    state.websocket.broadcast(Broadcast::TeamUpdate {
-        teams: vec![TeamUpdate {
-            id: user_id,
-            name: user.username.clone(),
-            display_name: user.display_name.clone(),
-            new_score,
-            new_states: states,
-        }],
-    });
+            state.websocket.broadcast_where(live, is_host);
+            state
+                .websocket
+                .broadcast_where(update(Some(cutoff)).await?, |who| !is_host(who));
+        }
+    }
 
     Ok(())
 }
@@ -377,7 +531,8 @@ pub struct CreatedSubmission {
 /// metadata). Afterward, the function will return, but the test may or may not still be
 /// running.
 ///
-/// A test can be cancelled via the `Tester::abort` method.
+/// A test can be cancelled via the `Tester::abort` method.  Returns `None` if the question does not
+/// exist or has not been released yet.
 pub async fn run_test(
     state: Arc<AppState>,
     language: String,
@@ -386,14 +541,183 @@ pub async fn run_test(
     test_only: bool,
     submitter: UserId,
 ) -> Option<CreatedSubmission> {
-    // NOTE: It's not great that we construct a test runner and then throw it await, but we can't
-    // move the test runner into the new task, so it's fine (constructing one is really cheap).
-    let runner = state.tester.runner(&language, question_index);
-    if runner.is_none() {
+    if !state.is_released(question_index) {
+        return None;
+    }
+
+    judge(
+        state,
+        Judgement {
+            id: SubmissionId::new(),
+            language,
+            question_index,
+            code,
+            test_only,
+            submitter,
+            rejudge: false,
+            finished: None,
+        },
+    )
+    .await
+}
+
+/// Judge a submission which is already in the database again, replacing its results.  It is
+/// scored as if it had been judged at the time that it was originally submitted.
+///
+/// Like [`run_test`], this returns once setup has completed.
+pub async fn rejudge(
+    state: Arc<AppState>,
+    submission: SubmissionHistory,
+) -> Option<CreatedSubmission> {
+    judge(state, Judgement::rejudge(submission, None)).await
+}
+
+/// Submissions to judge again, for example after the tests of a problem have been corrected
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Rejudge {
+    /// Every submission for the question at this index
+    Question(usize),
+    /// Every submission made by this team
+    Team(UserId),
+    /// Only these submissions
+    Submissions(Vec<SubmissionId>),
+}
+
+impl Rejudge {
+    /// Get the submissions which should be judged again, in the order in which they were made.
+    /// Submissions which do not exist or are still being judged are skipped.
+    pub async fn submissions(
+        &self,
+        db: impl SqliteExecutor<'_> + Copy,
+    ) -> anyhow::Result<Vec<SubmissionHistory>> {
+        match self {
+            Rejudge::Question(index) => {
+                repositories::submissions::get_judged_submissions(db, Some(*index), None).await
+            }
+            Rejudge::Team(id) => {
+                repositories::submissions::get_judged_submissions(db, None, Some(id)).await
+            }
+            Rejudge::Submissions(ids) => {
+                let mut submissions = Vec::with_capacity(ids.len());
+                let mut seen = HashSet::with_capacity(ids.len());
+                for id in ids.iter().filter(|id| seen.insert(**id)) {
+                    match repositories::submissions::get_submission(db, *id).await? {
+                        Some(s) if s.state != SubmissionState::Started => submissions.push(s),
+                        Some(_) => warn!(?id, "Not rejudging submission which is being judged"),
+                        None => warn!(?id, "Not rejudging submission which does not exist"),
+                    }
+                }
+                submissions.sort_by_key(|s| s.time);
+                Ok(submissions)
+            }
+        }
+    }
+}
+
+/// Judge `submissions` again one at a time, in order.  Each submission is only started once the
+/// previous one has finished, so that scores which depend on earlier submissions are computed
+/// from their new results.  Returns the submissions which were judged again.
+pub async fn rejudge_all(
+    state: Arc<AppState>,
+    submissions: Vec<SubmissionHistory>,
+) -> Vec<SubmissionId> {
+    let mut rejudged = Vec::with_capacity(submissions.len());
+    for submission in submissions {
+        let id = submission.id;
+        let (finished_tx, finished_rx) = oneshot::channel();
+        let judgement = Judgement::rejudge(submission, Some(finished_tx));
+        if judge(Arc::clone(&state), judgement).await.is_none() {
+            warn!(?id, "Could not rejudge submission");
+            continue;
+        }
+        let _ = finished_rx.await;
+        rejudged.push(id);
+    }
+    rejudged
+}
+
+/// Dispatch [`ServerEvent::OnComplete`] if `submission` is the one with which its submitter
+/// solved the last of the problems in the packet.  Completions are recorded, so a repeated or
+/// rejudged solution does not complete the packet a second time.
+async fn dispatch_completion(state: &Arc<AppState>, submission: &SubmissionHistory) {
+    let solves =
+        match repositories::submissions::get_first_solves(&state.db, &submission.submitter).await {
+            Ok(solves) => solves,
+            Err(error) => {
+                error!(?error, "Error getting solves of submitter");
+                return;
+            }
+        };
+    if solves.len() < state.config.packet.problems.len() {
+        return;
+    }
+
+    match repositories::submissions::record_completion(&state.db, &submission.submitter).await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(error) => {
+            error!(?error, "Error recording completion of submitter");
+            return;
+        }
+    }
+
+    if let Err(err) = (ServerEvent::OnComplete {
+        id: submission.submitter,
+        time: utils::utc_now(),
+    }
+    .dispatch(state.clone()))
+    {
+        error!("error dispatching completion event: {:?}", err);
+    }
+}
+
+struct Judgement {
+    id: SubmissionId,
+    language: String,
+    question_index: usize,
+    code: String,
+    test_only: bool,
+    submitter: UserId,
+    /// Whether the submission is already in the database, in which case it is scored as of when
+    /// it was made
+    rejudge: bool,
+    /// Notified once judging has completed, including sending results to the team
+    finished: Option<oneshot::Sender<()>>,
+}
+
+impl Judgement {
+    fn rejudge(submission: SubmissionHistory, finished: Option<oneshot::Sender<()>>) -> Self {
+        Self {
+            id: submission.id,
+            language: submission.language,
+            question_index: submission.question_index as usize,
+            code: submission.code,
+            test_only: submission.test_only,
+            submitter: submission.submitter,
+            rejudge: true,
+            finished,
+        }
+    }
+}
+
+async fn judge(state: Arc<AppState>, judgement: Judgement) -> Option<CreatedSubmission> {
+    let Judgement {
+        id,
+        language,
+        question_index,
+        code,
+        test_only,
+        submitter,
+        rejudge,
+        finished,
+    } = judgement;
+
+    if state.tester.run_limits(&language, question_index).is_none() {
         // This should be prevented by the UI
         error!(
             language,
-            question_index, "Missing runner for attempted test"
+            question_index, "Language is not available for attempted test"
         );
         return None;
     };
@@ -405,24 +729,26 @@ pub async fn run_test(
 
     let (setup_tx, setup_rx) = oneshot::channel();
     let (abort_tx, abort_rx) = oneshot::channel();
-    let id = SubmissionId::new();
     state.tester.add_abort_handle(id, abort_tx);
-    tokio::spawn(async move {
+    let tasks = state.tester.tasks.clone();
+    tasks.spawn(async move {
         let language = language.as_ref();
         let code = code.as_ref();
-        let (runner, source_file) = state
+        let (run_limits, timeout) = state
             .tester
-            .runner(language, question_index)
-            .expect("runner should be Some according to check above, but was found to be None");
-
-        let compiled = runner
-            .file(BorrowedFileContent::string(code), source_file)
-            .filter_tests(if test_only {
-                |t| t.data().visible
-            } else {
-                |_| true
-            })
-            .compile()
+            .run_limits(language, question_index)
+            .expect("limits should be Some according to check above, but were found to be None");
+        let compiled = state
+            .tester
+            .compile(
+                state
+                    .config
+                    .languages
+                    .get_by_str(language)
+                    .expect("language should exist since it has limits"),
+                code,
+                run_limits,
+            )
             .await;
 
         let result_tx =
@@ -442,34 +768,64 @@ pub async fn run_test(
         //     Ok(false) - We're done with the test and TestComplete should be sent on ws
         //     Err(_)    - There was an error in the test, TestError should be sent on ws
         let result = async {
-            let other_completions =
-                repositories::submissions::count_other_submissions(&state.db, question_index)
-                    .await
-                    .map_err(|error| error!(?error, "Error counting other submissions"))?;
-
-            let previous_attempts = repositories::submissions::count_previous_submissions(
-                &state.db,
-                &submitter,
-                question_index,
-            )
-            .await
-            .map_err(|error| error!(?error, "Error counting previous submissions"))?;
+            let (other_completions, previous_attempts) = if rejudge {
+                (
+                    repositories::submissions::count_other_submissions_before(
+                        &state.db,
+                        question_index,
+                        &id,
+                    )
+                    .await,
+                    repositories::submissions::count_previous_submissions_before(
+                        &state.db,
+                        &submitter,
+                        question_index,
+                        &id,
+                    )
+                    .await,
+                )
+            } else {
+                (
+                    repositories::submissions::count_other_submissions(&state.db, question_index)
+                        .await,
+                    repositories::submissions::count_previous_submissions(
+                        &state.db,
+                        &submitter,
+                        question_index,
+                    )
+                    .await,
+                )
+            };
+            let other_completions = other_completions
+                .map_err(|error| error!(?error, "Error counting other submissions"))?;
+            let previous_attempts = previous_attempts
+                .map_err(|error| error!(?error, "Error counting previous submissions"))?;
 
             let compiled = match compiled {
-                Err(CompileError::CompileFail(compile_result)) => {
-                    repositories::submissions::create_failed_submission_history(
-                        &state.db,
-                        repositories::submissions::NewSubmissionHistory {
+                Ok(Err(compile_result)) => {
+                    if rejudge {
+                        repositories::submissions::reset_submission(
+                            &state.db,
                             id,
-                            submitter,
-                            code,
-                            question_index,
-                            language,
-                            compile_result: Some(&compile_result),
-                            test_only,
-                        },
-                    )
-                    .await
+                            Some(compile_result),
+                            SubmissionState::Failed,
+                        )
+                        .await
+                    } else {
+                        repositories::submissions::create_failed_submission_history(
+                            &state.db,
+                            repositories::submissions::NewSubmissionHistory {
+                                id,
+                                submitter,
+                                code,
+                                question_index,
+                                language,
+                                compile_result: Some(compile_result),
+                                test_only,
+                            },
+                        )
+                        .await
+                    }
                     .map_err(|error| error!(?error, "Error creating failed submission history"))?;
 
                     let _ = result_tx.send(TestWsSend::CompileFailed);
@@ -478,93 +834,139 @@ pub async fn run_test(
                 }
                 Err(error) => {
                     error!(?error, "Error spawning compile command");
+                    if rejudge {
+                        if let Err(error) = repositories::submissions::reset_submission(
+                            &state.db,
+                            id,
+                            None,
+                            SubmissionState::Failed,
+                        )
+                        .await
+                        {
+                            error!(?error, "Error updating submission to failed in database");
+                        }
+                    }
                     return Err(Unit);
                 }
-                Ok(compiled) => compiled,
+                Ok(Ok(compiled)) => compiled,
             };
 
-            if let Some(compile_result) = compiled.compile_result() {
+            if let Some(compile_result) = compiled.compile_output() {
                 let _ = result_tx.send(TestWsSend::Compiled {
-                    stdout: compile_result.stdout().to_str_lossy().into_owned(),
-                    stderr: compile_result.stderr().to_str_lossy().into_owned(),
+                    stdout: compile_result.stdout.into_owned(),
+                    stderr: compile_result.stderr.into_owned(),
                 });
             }
 
-            let submission = repositories::submissions::create_submission_history(
-                &state.db,
-                repositories::submissions::NewSubmissionHistory {
+            let submission = if rejudge {
+                repositories::submissions::reset_submission(
+                    &state.db,
                     id,
-                    submitter,
-                    code,
-                    question_index,
-                    language,
-                    compile_result: compiled.compile_result(),
-                    test_only,
-                },
-            )
-            .await
+                    compiled.compile_output(),
+                    SubmissionState::Started,
+                )
+                .await
+                .map(PartialSubmissionHistory)
+            } else {
+                repositories::submissions::create_submission_history(
+                    &state.db,
+                    repositories::submissions::NewSubmissionHistory {
+                        id,
+                        submitter,
+                        code,
+                        question_index,
+                        language,
+                        compile_result: compiled.compile_output(),
+                        test_only,
+                    },
+                )
+                .await
+            }
             .map_err(|error| error!(?error, "Error adding submission to database"))?;
 
             let _ = setup_tx.send(());
 
-            let mut handle = compiled.run();
+            let settings = state.settings.problem(question_index);
+            let checker = settings.map(|p| &p.checker).unwrap_or(&Checker::Exact);
+            let interactor = settings.and_then(|p| p.interactor.as_ref());
+            let problem = &state.config.packet.problems[question_index];
 
             let start = Instant::now();
             let mut passed = 0;
             let mut failed = 0;
-            while let Some(result) = handle
-                .wait_next()
-                .await
-                .map_err(|error| error!(?error, "Error running test"))?
-            {
-                if result.state() == TestResultState::Pass {
-                    passed += 1;
-                } else {
-                    failed += 1;
-                }
+            // stores the result of a single test and sends it to the team
+            let mut record =
+                async |index: usize, new: repositories::submissions::NewTestResults<'_>| {
+                    if new.result == DbTestResultState::Pass {
+                        passed += 1;
+                    } else {
+                        failed += 1;
+                    }
 
-                let res = repositories::submissions::create_test_results(
-                    &state.db,
-                    &submission.id,
-                    result.index(),
-                    (&result).into(),
-                )
-                .await;
+                    let res = repositories::submissions::create_test_results(
+                        &state.db,
+                        &submission.id,
+                        index,
+                        new,
+                    )
+                    .await;
 
-                if let Err(error) = res {
-                    error!(?error, "Error adding submission test to database");
-                    if let Err(error) = submission.fail(&state.db).await {
-                        error!(?error, "Error updating submission to failed in database");
+                    let stored = match res {
+                        Ok(stored) => stored,
+                        Err(error) => {
+                            error!(?error, "Error adding submission test to database");
+                            if let Err(error) = submission.clone().fail(&state.db).await {
+                                error!(?error, "Error updating submission to failed in database");
+                            }
+                            return Err(Unit);
+                        }
+                    };
+
+                    if let Err(err) = (ServerEvent::OnSubmissionEvaluation {
+                        id: submitter,
+                        question_idx: question_index as u32,
+                        question_text: problem.title.clone(),
+                        test_results: stored.clone().into(),
+                        time: utils::utc_now(),
+                    }
+                    .dispatch(state.clone()))
+                    {
+                        tracing::error!("error dispatching submission event: {:?}", err);
                     }
-                    return Err(Unit);
-                };
 
-                if let Err(err) = (ServerEvent::OnSubmissionEvaluation {
-                    id: submitter,
-                    question_idx: question_index as u32,
-                    question_text: state.config.packet.problems[question_index].title.clone(),
-                    test_results: (&result).into(),
-                    time: utils::utc_now(),
-                }
-                .dispatch(state.clone()))
-                {
-                    tracing::error!("error dispatching submission event: {:?}", err);
-                }
+                    let _ = result_tx.send(TestWsSend::Result(stored));
+                    Ok(())
+                };
 
-                let _ = result_tx.send(TestWsSend::Result(result));
+            let tests = problem
+                .tests
+                .iter()
+                .enumerate()
+                .filter(|(_, t)| !test_only || t.visible);
+            let mut results = state
+                .tester
+                .run_tests(&compiled, interactor, checker, tests, timeout);
+            while let Some((index, new)) = results.next().await {
+                let new = new.map_err(|error| error!(?error, "Error running test"))?;
+                record(index, new).await?;
             }
 
             let elapsed = start.elapsed();
-            let score = state.config.score(
-                question_index,
-                bedrock::scoring::EvaluationContext {
-                    num_completions: other_completions,
-                    num_attempts: previous_attempts,
-                    passed_tests: passed,
-                    failed_tests: failed,
-                    number_tests: passed + failed,
-                },
-            );
+            let score = if state.is_race() {
+                // in a race, a problem is either solved or it isn't
+                Ok(if failed == 0 { 1. } else { 0. })
+            } else {
+                state.config.score(
+                    question_index,
+                    bedrock::scoring::EvaluationContext {
+                        num_completions: other_completions,
+                        num_attempts: previous_attempts,
+                        passed_tests: passed,
+                        failed_tests: failed,
+                        number_tests: passed + failed,
+                    },
+                )
+            };
 
             let score = match score {
                 Ok(score) => score,
@@ -577,11 +979,15 @@ pub async fn run_test(
                 }
             };
 
-            submission
+            let finished = submission
                 .finish(&state.db, score, failed == 0, passed, failed, elapsed)
                 .await
                 .map_err(|error| error!(?error, "Error updating submission in database"))?;
 
+            if finished.success && !finished.test_only {
+                dispatch_completion(&state, &finished).await;
+            }
+
             Ok::<_, Unit>(false)
         };
 
@@ -614,8 +1020,42 @@ pub async fn run_test(
         };
 
         let _ = broadcast_team_update(&state, submitter).await;
+        if let Some(finished) = finished {
+            let _ = finished.send(());
+        }
     });
     let _ = setup_rx.await;
 
     Some(CreatedSubmission { id, cases })
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{db_user, mock_db, submissions_repositories::dummy_submission, SAMPLE_1};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn packet_is_only_completed_once() {
+        let db = mock_db().await;
+        let team = db_user(&db, "team", Role::Competitor).await;
+        let cfg = Config::from_str(SAMPLE_1, Some("single.toml")).unwrap();
+        let mut state = AppState::new(db, cfg, None);
+        state.init().await.unwrap();
+        let (tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+        state.dispatchers.push(tx);
+        let state = Arc::new(state);
+
+        // the sample packet has a single problem
+        let first = dummy_submission(&state.db, &team, 10., 0).await;
+        dispatch_completion(&state, &first).await;
+        let (event, _) = events.try_recv().unwrap();
+        assert!(matches!(event, ServerEvent::OnComplete { id, .. } if id == team.id));
+
+        // neither solving the problem again nor rejudging the solution completes the packet again
+        let repeated = dummy_submission(&state.db, &team, 10., 0).await;
+        dispatch_completion(&state, &repeated).await;
+        dispatch_completion(&state, &first).await;
+        assert!(events.try_recv().is_err());
+    }
+}
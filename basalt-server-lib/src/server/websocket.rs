@@ -61,13 +61,28 @@ impl ConnectedClient {
 pub struct WebSocketManager {
     active_connections: DashMap<ConnectionKind, ConnectedClient>,
     waiting_connections: DashMap<UserId, Vec<oneshot::Sender<ConnectedClient>>>,
+    /// Messages for users that were not connected when they were sent, delivered once they connect
+    pending_messages: DashMap<UserId, Vec<WebSocketSend>>,
 }
 
 impl WebSocketManager {
     pub fn broadcast(&self, broadcast: Broadcast) {
-        let ws = WebSocketSend::Broadcast(broadcast);
+        self.broadcast_where(broadcast, |_| true);
+    }
+
+    /// Broadcast a message to only the connections for which `filter` returns true
+    pub fn broadcast_where(&self, broadcast: Broadcast, filter: impl Fn(&ConnectionKind) -> bool) {
+        self.broadcast_each(|who| filter(who).then(|| broadcast.clone()));
+    }
+
+    /// Broadcast a message which depends on the connection to which it is sent.  Nothing is sent
+    /// to the connections for which `message` returns `None`.
+    pub fn broadcast_each(&self, message: impl Fn(&ConnectionKind) -> Option<Broadcast>) {
         self.active_connections.retain(|key, conn| {
-            match conn.send(ws.clone()) {
+            let Some(broadcast) = message(key) else {
+                return true;
+            };
+            match conn.send(WebSocketSend::Broadcast(broadcast)) {
                 Ok(()) => true,
                 Err(_) => {
                     tracing::warn!(?key, "Socket discovered to be closed when sending broadcast. Removing from active connections...");
@@ -84,8 +99,14 @@ impl WebSocketManager {
     pub fn add_connection(&self, who: ConnectionKind) -> mpsc::UnboundedReceiver<WebSocketSend> {
         let (tx, rx) = mpsc::unbounded_channel();
         let connected = ConnectedClient { tx };
-        // If this is a user, alert anybody waiting
+        // If this is a user, deliver anything queued for them and alert anybody waiting
         if let ConnectionKind::User { ref user } = who {
+            if let Some((_, messages)) = self.pending_messages.remove(user) {
+                for message in messages {
+                    let _ = connected.send(message);
+                }
+            }
+
             if let Some((_, senders)) = self.waiting_connections.remove(user) {
                 for sender in senders {
                     let _ = sender.send(connected.clone());
@@ -115,6 +136,18 @@ impl WebSocketManager {
         }
     }
 
+    /// Send a message to a user, or hold onto it until they next connect if they are not connected
+    pub fn send_or_queue(&self, user: UserId, message: WebSocketSend) {
+        let message = match self.active_connections.get(&ConnectionKind::User { user }) {
+            Some(conn) => match conn.send(message) {
+                Ok(()) => return,
+                Err(err) => err.0,
+            },
+            None => message,
+        };
+        self.pending_messages.entry(user).or_default().push(message);
+    }
+
     pub fn get_sender(&self, who: &'_ ConnectionKind) -> Option<ConnectedClient> {
         self.active_connections.get(who).as_deref().cloned()
     }
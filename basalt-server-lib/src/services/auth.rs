@@ -12,7 +12,7 @@ use crate::{
         session::SessionId,
         users::{Role, User, UserLogin},
     },
-    server::{hooks::events::ServerEvent, teams::TeamWithScore, AppState},
+    server::{hooks::events::ServerEvent, standings, teams::TeamWithScore, AppState},
     services::ws::Broadcast,
 };
 
@@ -55,7 +55,9 @@ async fn login(
     let token = repositories::session::create_session(&state.db, &user)
         .await
         .unwrap();
-    let score = repositories::submissions::get_user_score(&state.db, &user.id)
+    // this is broadcast to everybody, so it must not reveal scores while the scoreboard is frozen
+    let cutoff = state.scoreboard_cutoff(None).await;
+    let score = standings::get_score(&state.db, &state.config.game, &user.id, cutoff)
         .await
         .unwrap();
 
@@ -115,7 +117,9 @@ async fn logout(
         .await
         .unwrap();
 
-    let score = repositories::submissions::get_user_score(&state.db, &user.id)
+    // this is broadcast to everybody, so it must not reveal scores while the scoreboard is frozen
+    let cutoff = state.scoreboard_cutoff(None).await;
+    let score = standings::get_score(&state.db, &state.config.game, &user.id, cutoff)
         .await
         .unwrap();
 
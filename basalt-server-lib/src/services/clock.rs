@@ -1,14 +1,15 @@
 use std::{sync::Arc, time::Duration};
 
 use axum::{extract::State, http::StatusCode, Json};
-use bedrock::{Game, PointsSettings};
+use chrono::{TimeDelta, Utc};
 use tracing::{error, trace};
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 use crate::{
     extractors::auth::{HostUser, OptionalUser},
+    repositories::{self, users::Role},
     server::{hooks::events::ServerEvent, AppState},
-    services::ws::Broadcast,
+    services::{questions, ws::Broadcast},
 };
 
 #[derive(serde::Deserialize, utoipa::ToSchema)]
@@ -16,6 +17,9 @@ use crate::{
 pub enum UpdateClockRequest {
     #[serde(rename_all(serialize = "camelCase", deserialize = "camelCase"))]
     PauseUpdate { is_paused: bool },
+    /// Add time to the competition, or remove it if negative
+    #[serde(rename_all(serialize = "camelCase", deserialize = "camelCase"))]
+    TimeUpdate { adjust_by_seconds: i64 },
 }
 
 #[derive(serde::Serialize, utoipa::ToSchema)]
@@ -31,6 +35,7 @@ struct ClockStatusResponse {
     path="/", tag="clock", request_body=UpdateClockRequest,
     responses(
         (status=OK, body=ClockStatusResponse, description="Game is paused"),
+        (status=BAD_REQUEST, description="Time adjustment is out of range"),
         (status=UNAUTHORIZED, description="Insufficient permissions"),
     )
 )]
@@ -39,23 +44,27 @@ async fn patch_clock(
     auth: HostUser,
     Json(update): Json<UpdateClockRequest>,
 ) -> Result<Json<ClockStatusResponse>, StatusCode> {
-    let time_limit = match &state.config.game {
-        &Game::Points(PointsSettings { time_limit, .. }) => time_limit,
-        // TODO: When other modes are supported, provide correct values
-        _ => Duration::from_secs(60 * 75),
-    };
+    let time_limit = state.time_limit();
 
     let (response, broadcast) = {
         let mut clock = state.clock.write().await;
         // this should never error ever
         let current_time = clock.current_time().unwrap();
-        match update {
+        let result = match update {
             UpdateClockRequest::PauseUpdate { is_paused: true } => {
-                let affected = clock.pause();
+                let now = Utc::now();
+                let affected = clock.pause_at(now);
                 if affected {
+                    repositories::clock::record_pause(&state.db, now)
+                        .await
+                        .map_err(|error| {
+                            error!(?error, "Failed to save clock");
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        })?;
+
                     if let Err(err) = (ServerEvent::OnPause {
                         paused_by: auth.id,
-                        time: chrono::offset::Local::now().to_utc(),
+                        time: now,
                     }
                     .dispatch(state.clone()))
                     {
@@ -75,16 +84,31 @@ async fn patch_clock(
                 )
             }
             UpdateClockRequest::PauseUpdate { is_paused: false } => {
-                let affected = clock.unpause();
+                let now = Utc::now();
+                let started = clock.has_started();
+                let affected = clock.unpause_at(now);
                 if affected {
+                    repositories::clock::record_unpause(&state.db, now, clock.total_time_paused)
+                        .await
+                        .map_err(|error| {
+                            error!(?error, "Failed to save clock");
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        })?;
+
                     if let Err(err) = (ServerEvent::OnUnpause {
                         unpaused_by: auth.id,
-                        time: chrono::offset::Local::now().to_utc(),
+                        time: now,
                     }
                     .dispatch(state.clone()))
                     {
                         error!("Failed to dispatch pause event: {:?}", err);
                     };
+
+                    if !started {
+                        state.websocket.broadcast(Broadcast::GameStarted {
+                            questions: questions::released_questions(&state).await,
+                        });
+                    }
                 }
                 let time_left_in_seconds = current_time.time_left(time_limit).as_secs();
                 (
@@ -101,7 +125,43 @@ async fn patch_clock(
                     },
                 )
             }
-        }
+            UpdateClockRequest::TimeUpdate { adjust_by_seconds } => {
+                let delta =
+                    TimeDelta::try_seconds(adjust_by_seconds).ok_or(StatusCode::BAD_REQUEST)?;
+                clock.adjust(delta);
+                repositories::clock::record_adjustment(&state.db, clock.time_adjustment)
+                    .await
+                    .map_err(|error| {
+                        error!(?error, "Failed to save clock");
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    })?;
+
+                let current_time = clock.current_time().unwrap();
+                let is_paused = current_time.paused;
+                let time_left_in_seconds = current_time.time_left(time_limit).as_secs();
+                if let Err(err) = (ServerEvent::OnTimeAdjusted {
+                    adjusted_by: auth.id,
+                    adjustment_in_seconds: adjust_by_seconds,
+                    time_left_in_seconds,
+                    time: Utc::now(),
+                }
+                .dispatch(state.clone()))
+                {
+                    error!("Failed to dispatch time adjustment event: {:?}", err);
+                };
+                (
+                    ClockStatusResponse {
+                        is_paused,
+                        time_left_in_seconds,
+                    },
+                    Some(Broadcast::TimeAdjusted {
+                        time_left_in_seconds,
+                    }),
+                )
+            }
+        };
+        state.broadcast_clock(&clock);
+        result
     };
 
     if let Some(broadcast) = broadcast {
@@ -116,23 +176,23 @@ async fn patch_clock(
     get,
     path="/", tag="clock",
     responses(
-        (status=OK, body=ClockStatusResponse, description="Information about the clock"),
+        (status=OK, body=ClockStatusResponse, description="Information about the clock, from the perspective of the requesting team"),
     )
 )]
 async fn get_clock(
-    OptionalUser(_): OptionalUser,
+    OptionalUser(user): OptionalUser,
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<ClockStatusResponse>, StatusCode> {
     trace!("user getting clock");
 
-    let time_limit = match state.config.game {
-        // TODO: When time_limit is made public, update this
-        Game::Points(PointsSettings { time_limit, .. }) => time_limit,
-        // TODO: When other modes are supported, provide correct values
-        _ => Duration::from_secs(60 * 75),
-    };
+    let time_limit = state.time_limit();
     let clock = state.clock.read().await;
-    match clock.current_time() {
+    // teams see their own time, which includes any time that they have been given
+    let current_time = match user {
+        Some(user) if user.role == Role::Competitor => clock.current_team_time(&user.id),
+        _ => clock.current_time(),
+    };
+    match current_time {
         Ok(current_time) => Ok(Json(ClockStatusResponse {
             is_paused: current_time.paused,
             time_left_in_seconds: current_time.time_left(time_limit).as_secs(),
@@ -1,21 +1,40 @@
-use crate::server::AppState;
+use crate::{
+    extractors::auth::{HostUser, OptionalUser},
+    repositories::users::Role,
+    server::AppState,
+    utils,
+};
 use axum::{
+    body::Body,
     extract::State,
     http::{header, StatusCode},
     response::{AppendHeaders, IntoResponse},
 };
 use std::sync::Arc;
 use tokio::sync::OnceCell;
-use tracing::{debug, error};
+use tokio_util::io::ReaderStream;
+use tracing::{debug, error, info};
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 static PDF: OnceCell<Box<[u8]>> = OnceCell::const_new();
 
 #[axum::debug_handler]
-#[utoipa::path(get, tag = "competition", path = "/packet", responses((status = OK, body = Vec<u8>, content_type = "application/pdf")))]
+#[utoipa::path(
+    get, tag = "competition", path = "/packet",
+    responses(
+        (status = OK, body = Vec<u8>, content_type = "application/pdf"),
+        (status = 409, description = "Competition has not started yet"),
+    )
+)]
 pub async fn download_packet(
+    OptionalUser(user): OptionalUser,
     State(state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, StatusCode> {
+    let is_host = user.is_some_and(|u| u.role == Role::Host);
+    if !is_host && !state.has_started().await {
+        return Err(StatusCode::CONFLICT);
+    }
+
     PDF.get_or_try_init(|| async {
         debug!("Rendering packet PDF");
         state.config.render_pdf(None).map(Vec::into_boxed_slice)
@@ -39,8 +58,65 @@ pub async fn download_packet(
     })
 }
 
+#[axum::debug_handler]
+#[utoipa::path(
+    post, tag = "competition", path = "/backup",
+    description = "Take a snapshot of the competition database.  A copy is also kept in the competition's data folder.",
+    responses(
+        (status = OK, body = Vec<u8>, content_type = "application/vnd.sqlite3"),
+        (status = 403, description = "User does not have permission to back up the database"),
+    )
+)]
+pub async fn backup(
+    State(state): State<Arc<AppState>>,
+    HostUser(host): HostUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let file_name = format!("backup-{}.db", utils::utc_now().format("%Y%m%d-%H%M%S%.3f"));
+    let (dest, keep) = match state.db.directory() {
+        Some(dir) => (dir.join("backups").join(&file_name), true),
+        None => (std::env::temp_dir().join(&file_name), false),
+    };
+
+    if let Some(parent) = dest.parent() {
+        tokio::fs::create_dir_all(parent).await.map_err(|error| {
+            error!(?error, "Error creating backup directory");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    }
+
+    state.db.snapshot(&dest).await.map_err(|error| {
+        error!(?error, "Error taking database snapshot");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let file = tokio::fs::File::open(&dest).await.map_err(|error| {
+        error!(?error, "Error opening database snapshot");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // the open file can still be streamed once a temporary snapshot has been removed
+    if keep {
+        info!(host = %host.username, path = ?dest, "Created database backup");
+    } else if let Err(error) = tokio::fs::remove_file(&dest).await {
+        error!(?error, "Error removing temporary database snapshot");
+    }
+
+    Ok((
+        AppendHeaders([
+            (header::CONTENT_TYPE, "application/vnd.sqlite3".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ]),
+        Body::from_stream(ReaderStream::new(file)),
+    ))
+}
+
 pub fn router() -> OpenApiRouter<Arc<AppState>> {
-    OpenApiRouter::new().routes(routes!(download_packet))
+    OpenApiRouter::new()
+        .routes(routes!(download_packet))
+        .routes(routes!(backup))
 }
 
 pub fn service() -> axum::Router<Arc<AppState>> {
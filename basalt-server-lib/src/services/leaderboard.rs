@@ -1,9 +1,10 @@
 use crate::{
+    extractors::auth::OptionalUser,
     repositories::{
         self,
         users::{QuestionState, Role, User},
     },
-    server::AppState,
+    server::{standings, AppState},
 };
 use axum::{extract::State, http::StatusCode, Json};
 use serde::Serialize;
@@ -16,6 +17,8 @@ use utoipa_axum::{router::OpenApiRouter, routes};
 #[serde(rename_all = "camelCase")]
 pub struct TeamProgression {
     user: User,
+    /// 1-based placement of the team, see [`standings::Standing::rank`]
+    rank: usize,
     score: f64,
     submission_states: Vec<QuestionState>,
 }
@@ -24,13 +27,14 @@ pub struct TeamProgression {
 #[utoipa::path(
     get, path = "/",
     tag = "leaderboard",
-    description = "Gets all team's submission states and total number of points",
+    description = "Gets all team's submission states and total number of points.  While the scoreboard is frozen, only hosts see changes made after the freeze.",
     responses(
         (status = OK, body = Vec<TeamProgression>, content_type = "application/json"),
         (status = 403, description = "User does not have permission to view the leaderboard"),
     ),
 )]
 pub async fn get_leaderboard_info(
+    OptionalUser(viewer): OptionalUser,
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<Vec<TeamProgression>>, StatusCode> {
     let competitors: Vec<User> =
@@ -41,63 +45,45 @@ pub async fn get_leaderboard_info(
                 StatusCode::INTERNAL_SERVER_ERROR
             })?;
 
+    let cutoff = state.scoreboard_cutoff(viewer.as_ref()).await;
+    let standings = standings::get_standings(&state.db, &state.config.game, cutoff)
+        .await
+        .map_err(|e| {
+            error!("Error while getting standings: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
     let mut leaderboard_info = Vec::with_capacity(competitors.len());
 
     for user in &competitors {
-        // Get list size and sets values to not-attempted by default
-        let mut submission_states =
-            vec![QuestionState::NotAttempted; state.config.packet.problems.len()];
-
-        let submissions =
-            match repositories::submissions::get_latest_submissions(&state.db, &user.id).await {
-                Ok(submissions) => submissions,
-                Err(err) => {
-                    tracing::error!("Error while getting submissions: {}", err);
-                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
-                }
-            };
-
-        for s in submissions {
-            submission_states[s.question_index as usize] = if s.success {
-                QuestionState::Pass
-            } else {
-                QuestionState::Fail
-            };
-        }
-
-        match repositories::submissions::count_tests(&state.db, &user.id).await {
-            Ok(counts) => {
-                for c in counts {
-                    if submission_states[c.question_index as usize] == QuestionState::NotAttempted {
-                        submission_states[c.question_index as usize] = if c.count > 0 {
-                            QuestionState::InProgress
-                        } else {
-                            QuestionState::NotAttempted
-                        };
-                    }
-                }
-            }
-            Err(err) => {
-                tracing::error!("Error while getting attempts: {}", err);
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
-            }
-        }
-
-        let score = match repositories::submissions::get_user_score(&state.db, &user.id).await {
-            Ok(score) => score,
-            Err(err) => {
-                tracing::error!("Error while getting score: {}", err);
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
-            }
+        let Some(standing) = standings.iter().find(|s| s.id == user.id) else {
+            // the user was created after the standings were computed
+            continue;
         };
 
+        let progress = standings::get_progress(
+            &state.db,
+            &state.config.game,
+            state.config.packet.problems.len(),
+            &user.id,
+            cutoff,
+        )
+        .await
+        .map_err(|e| {
+            error!("Error while getting progress: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
         leaderboard_info.push(TeamProgression {
             user: user.clone(),
-            score,
-            submission_states,
+            rank: standing.rank,
+            score: standing.score,
+            submission_states: progress.states,
         });
     }
 
+    leaderboard_info.sort_by_key(|t| t.rank);
+
     Ok(Json(leaderboard_info))
 }
 
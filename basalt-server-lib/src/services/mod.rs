@@ -4,6 +4,8 @@ pub mod clock;
 pub mod competition;
 pub mod leaderboard;
 pub mod questions;
+pub mod rejudge;
+pub mod resolver;
 pub mod teams;
 pub mod testing;
 pub mod ws;
@@ -1,11 +1,12 @@
 use crate::{
-    extractors::auth::OptionalUser,
+    extractors::auth::{HostUser, OptionalUser},
     repositories::{
         self,
         submissions::{SubmissionHistory, SubmissionId, TestResults},
         users::{Role, User},
     },
     server::{tester::CreatedSubmission, AppState},
+    settings::{ProblemLimits, Settings},
 };
 use axum::{
     extract::State,
@@ -13,14 +14,14 @@ use axum::{
     Json,
 };
 use bedrock::{
-    language::{Language, LanguageSet, Syntax},
+    language::{Language, Syntax},
     packet::{Problem, Test},
     Config,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::OnceCell;
-use tracing::error;
+use tracing::{error, info};
 use utoipa::ToSchema;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
@@ -36,6 +37,10 @@ pub struct LanguageSyntax {
     name: String,
     #[schema(value_type = String)]
     syntax: Syntax,
+    /// Time limit of each test in milliseconds when the question is solved in this language
+    time_limit: u64,
+    /// Memory limit of each test in MiB when the question is solved in this language
+    memory_limit: u64,
 }
 
 impl From<&Test> for TestResponse {
@@ -48,41 +53,57 @@ impl From<&Test> for TestResponse {
     }
 }
 
-impl From<&Language> for LanguageSyntax {
-    fn from(value: &Language) -> Self {
+impl LanguageSyntax {
+    fn new(language: &Language, limits: ProblemLimits) -> Self {
         Self {
-            name: value.name().to_string(),
-            syntax: value.syntax(),
+            name: language.name().to_string(),
+            syntax: language.syntax(),
+            time_limit: limits.time.as_millis() as u64,
+            memory_limit: limits.memory,
         }
     }
 }
 
 #[derive(Debug, PartialEq, Eq, serde::Serialize, utoipa::ToSchema)]
 pub struct QuestionResponse {
+    /// Index of the question in the packet
+    index: usize,
     languages: Vec<LanguageSyntax>,
     title: String,
     description: Option<String>,
     tests: Vec<TestResponse>,
     points: Option<i32>,
+    /// Time limit of each test in milliseconds, before it is multiplied for any language
+    time_limit: u64,
+    /// Memory limit of each test in MiB, before it is multiplied for any language
+    memory_limit: u64,
 }
 
 impl QuestionResponse {
     fn from(
+        index: usize,
         value: &Problem,
-        languages: &LanguageSet,
+        config: &Config,
+        settings: &Settings,
         default_points: Option<i32>,
         show_hidden: bool,
     ) -> Self {
+        let languages = &config.languages;
+        let syntax = |l: &Language| {
+            LanguageSyntax::new(l, settings.limits(config, index, Some(l.raw_name())))
+        };
+        let limits = settings.limits(config, index, None);
         Self {
+            index,
             languages: value
                 .languages
                 .as_ref()
                 .map(|p| {
                     p.iter()
-                        .map(|l| languages.get_by_str(l).unwrap().into())
+                        .map(|l| syntax(languages.get_by_str(l).unwrap()))
                         .collect()
                 })
-                .unwrap_or_else(|| languages.iter().map(LanguageSyntax::from).collect()),
+                .unwrap_or_else(|| languages.iter().map(syntax).collect()),
             title: value.title.clone(),
             description: value.description.as_ref().map(|x| x.html().unwrap()),
             tests: value
@@ -92,17 +113,21 @@ impl QuestionResponse {
                 .filter(|t| show_hidden || t.visible)
                 .collect(),
             points: value.points.or(default_points),
+            time_limit: limits.time.as_millis() as u64,
+            memory_limit: limits.memory,
         }
     }
 }
 
-// Questions with test cases hidden
+// Questions with test cases hidden.  These include problems which have not been released yet, so
+// they must be filtered with `AppState::is_released` before being shown to competitors.
 static QUESTIONS_VISIBLE: OnceCell<Vec<QuestionResponse>> = OnceCell::const_new();
 // Questions with all test cases
 static QUESTIONS_FULL: OnceCell<Vec<QuestionResponse>> = OnceCell::const_new();
 
 pub async fn get_or_init_questions(
     config: &Config,
+    settings: &Settings,
     show_hidden: bool,
 ) -> &'static [QuestionResponse] {
     let questions = if show_hidden {
@@ -117,13 +142,17 @@ pub async fn get_or_init_questions(
                 .packet
                 .problems
                 .iter()
-                .map(|x| {
+                .enumerate()
+                .map(|(i, x)| {
                     QuestionResponse::from(
+                        i,
                         x,
-                        &config.languages,
+                        config,
+                        settings,
                         match &config.game {
                             bedrock::Game::Points(x) => Some(x.question_point_value),
-                            bedrock::Game::Race(_) => None,
+                            // every question counts as a single solve in a race
+                            bedrock::Game::Race(_) => Some(1),
                         },
                         show_hidden,
                     )
@@ -133,16 +162,38 @@ pub async fn get_or_init_questions(
         .await
 }
 
+/// Questions which competitors may see, without their hidden tests
+pub async fn released_questions(state: &AppState) -> Vec<&'static QuestionResponse> {
+    get_or_init_questions(&state.config, &state.settings, false)
+        .await
+        .iter()
+        .filter(|q| state.is_released(q.index))
+        .collect()
+}
+
 #[axum::debug_handler]
-#[utoipa::path(get, tag = "questions", path = "/", responses((status = OK, body = &[QuestionResponse], content_type = "application/json")))]
+#[utoipa::path(
+    get, tag = "questions", path = "/",
+    responses(
+        (status = OK, body = &[QuestionResponse], content_type = "application/json"),
+        (status = 409, description = "Competition has not started yet"),
+    )
+)]
 pub async fn get_all(
     OptionalUser(user): OptionalUser,
     State(state): State<Arc<AppState>>,
-) -> Json<&'static [QuestionResponse]> {
+) -> Result<Json<Vec<&'static QuestionResponse>>, StatusCode> {
     let show_hidden = user.is_some_and(|u| matches!(u.role, Role::Host));
-    let questions = get_or_init_questions(&state.config, show_hidden).await;
+    if !show_hidden && !state.has_started().await {
+        return Err(StatusCode::CONFLICT);
+    }
 
-    Json(questions)
+    if show_hidden {
+        let questions = get_or_init_questions(&state.config, &state.settings, true).await;
+        return Ok(Json(questions.iter().collect()));
+    }
+
+    Ok(Json(released_questions(&state).await))
 }
 
 #[axum::debug_handler]
@@ -152,6 +203,7 @@ pub async fn get_all(
     responses(
         (status=OK, body=QuestionResponse, content_type="application/json"),
         (status=404, description="Question Not Found"),
+        (status=409, description="Competition has not started yet"),
     )
 )]
 pub async fn get_specific_question(
@@ -160,13 +212,52 @@ pub async fn get_specific_question(
     axum::extract::Path(question): axum::extract::Path<usize>,
 ) -> Result<Json<&'static QuestionResponse>, StatusCode> {
     let show_hidden = user.is_some_and(|u| matches!(u.role, Role::Host));
-    get_or_init_questions(&state.config, show_hidden)
+    if !show_hidden && !state.has_started().await {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    if !show_hidden && !state.is_released(question) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    get_or_init_questions(&state.config, &state.settings, show_hidden)
         .await
         .get(question)
         .map(Json)
         .ok_or(StatusCode::NOT_FOUND)
 }
 
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/{id}/release", tag = "questions",
+    description = "Release a question which is hidden until it is released, regardless of its schedule",
+    responses(
+        (status=OK, body=bool, content_type="application/json", description="Whether the question was released by this request"),
+        (status=403, description="User does not have permission to release questions"),
+        (status=404, description="Question Not Found"),
+    )
+)]
+pub async fn release_question(
+    State(state): State<Arc<AppState>>,
+    HostUser(host): HostUser,
+    axum::extract::Path(question): axum::extract::Path<usize>,
+) -> Result<Json<bool>, StatusCode> {
+    if question >= state.config.packet.problems.len() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let released = state.release_problem(question).await.map_err(|error| {
+        error!(?error, "Error releasing question");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if released {
+        info!(host = %host.username, question, "Question released by host");
+    }
+
+    Ok(Json(released))
+}
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct SubmissionBody {
     language: String,
@@ -182,6 +273,8 @@ pub struct SubmissionBody {
         (status=400, description="Invalid data provided"),
         (status=404, description="Question or language not found"),
         (status=409, description="Competition is paused"),
+        (status=423, description="Competition is over"),
+        (status=503, description="Server is shutting down"),
     )
 )]
 #[axum::debug_handler]
@@ -191,6 +284,15 @@ pub async fn create_submission(
     State(state): State<Arc<AppState>>,
     Json(body): Json<SubmissionBody>,
 ) -> Result<(StatusCode, HeaderMap, Json<CreatedSubmission>), StatusCode> {
+    if state.is_shutting_down() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    // checked before pausing, since the clock is paused once the game is over
+    if state.is_game_over_for(&user.id).await {
+        return Err(StatusCode::LOCKED);
+    }
+
     if state.is_paused().await {
         return Err(StatusCode::CONFLICT);
     }
@@ -229,6 +331,8 @@ pub async fn create_submission(
         (status=400, description="Invalid data provided"),
         (status=404, description="Question or language not found"),
         (status=409, description="Competition is paused"),
+        (status=423, description="Competition is over"),
+        (status=503, description="Server is shutting down"),
     )
 )]
 #[axum::debug_handler]
@@ -238,6 +342,15 @@ pub async fn create_test(
     State(state): State<Arc<AppState>>,
     Json(body): Json<SubmissionBody>,
 ) -> Result<(StatusCode, HeaderMap, Json<CreatedSubmission>), StatusCode> {
+    if state.is_shutting_down() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    // checked before pausing, since the clock is paused once the game is over
+    if state.is_game_over_for(&user.id).await {
+        return Err(StatusCode::LOCKED);
+    }
+
     if state.is_paused().await {
         return Err(StatusCode::CONFLICT);
     }
@@ -489,6 +602,7 @@ pub fn router() -> OpenApiRouter<Arc<AppState>> {
     OpenApiRouter::new()
         .routes(routes!(get_all))
         .routes(routes!(get_specific_question))
+        .routes(routes!(release_question))
         .routes(routes!(create_submission))
         .routes(routes!(create_test))
         .routes(routes!(get_submission))
@@ -511,11 +625,12 @@ mod test {
             submissions::{NewTestResults, SubmissionState, TestResultState},
             users::UserId,
         },
+        settings::ProblemSettings,
         testing::{db_user, setup_test_logger},
         user,
     };
     use bedrock::{
-        language::{BuiltInLanguage, Version},
+        language::{BuiltInLanguage, LanguageSet, Version},
         packet::Packet,
         roi::RawOrImport,
     };
@@ -523,6 +638,10 @@ mod test {
 
     use super::*;
 
+    fn default_limits() -> ProblemLimits {
+        Settings::default().limits(&Config::default(), 0, None)
+    }
+
     fn double_problem_packet() -> RawOrImport<Packet> {
         Packet {
             title: "".into(),
@@ -642,13 +761,17 @@ mod test {
         let state = mock_state! {
             packet: double_problem_packet(),
         };
+        state.clock.write().await.unpause();
 
-        let Json(value) = get_all(user!("foobar", Competitor).into(), State(state)).await;
+        let Json(value) = get_all(user!("foobar", Competitor).into(), State(state))
+            .await
+            .unwrap();
 
         assert_eq!(
             value,
             vec![
-                QuestionResponse {
+                &QuestionResponse {
+                    index: 0,
                     languages: vec![],
                     title: "problem1".into(),
                     description: None,
@@ -657,9 +780,12 @@ mod test {
                         output: "output1a".into(),
                         visible: true,
                     }],
-                    points: Some(10)
+                    points: Some(10),
+                    time_limit: default_limits().time.as_millis() as u64,
+                    memory_limit: default_limits().memory,
                 },
-                QuestionResponse {
+                &QuestionResponse {
+                    index: 1,
                     languages: vec![],
                     title: "problem2".into(),
                     description: None,
@@ -668,7 +794,9 @@ mod test {
                         output: "output2b".into(),
                         visible: true,
                     }],
-                    points: Some(10)
+                    points: Some(10),
+                    time_limit: default_limits().time.as_millis() as u64,
+                    memory_limit: default_limits().memory,
                 }
             ]
         );
@@ -681,12 +809,15 @@ mod test {
         let state = mock_state! {
             packet: double_problem_packet(),
         };
-        let Json(value) = get_all(user!("foobar", Host).into(), State(state)).await;
+        let Json(value) = get_all(user!("foobar", Host).into(), State(state))
+            .await
+            .unwrap();
 
         assert_eq!(
             value,
             vec![
-                QuestionResponse {
+                &QuestionResponse {
+                    index: 0,
                     languages: vec![],
                     title: "problem1".into(),
                     description: None,
@@ -702,9 +833,12 @@ mod test {
                             visible: false,
                         },
                     ],
-                    points: Some(10)
+                    points: Some(10),
+                    time_limit: default_limits().time.as_millis() as u64,
+                    memory_limit: default_limits().memory,
                 },
-                QuestionResponse {
+                &QuestionResponse {
+                    index: 1,
                     languages: vec![],
                     title: "problem2".into(),
                     description: None,
@@ -720,7 +854,9 @@ mod test {
                             visible: true,
                         },
                     ],
-                    points: Some(10)
+                    points: Some(10),
+                    time_limit: default_limits().time.as_millis() as u64,
+                    memory_limit: default_limits().memory,
                 }
             ]
         );
@@ -745,6 +881,7 @@ mod test {
         assert_eq!(
             *value,
             QuestionResponse {
+                index: 1,
                 languages: vec![],
                 title: "problem2".into(),
                 description: None,
@@ -760,7 +897,9 @@ mod test {
                         visible: true,
                     },
                 ],
-                points: Some(10)
+                points: Some(10),
+                time_limit: default_limits().time.as_millis() as u64,
+                memory_limit: default_limits().memory,
             }
         );
     }
@@ -772,6 +911,7 @@ mod test {
         let state = mock_state! {
             packet: double_problem_packet(),
         };
+        state.clock.write().await.unpause();
 
         let Json(value) = get_specific_question(
             State(state),
@@ -784,6 +924,7 @@ mod test {
         assert_eq!(
             *value,
             QuestionResponse {
+                index: 1,
                 languages: vec![],
                 title: "problem2".into(),
                 description: None,
@@ -792,7 +933,9 @@ mod test {
                     output: "output2b".into(),
                     visible: true,
                 }],
-                points: Some(10)
+                points: Some(10),
+                time_limit: default_limits().time.as_millis() as u64,
+                memory_limit: default_limits().memory,
             }
         );
     }
@@ -804,6 +947,7 @@ mod test {
         let state = mock_state! {
             packet: double_problem_packet(),
         };
+        state.clock.write().await.unpause();
 
         let code = get_specific_question(
             State(state),
@@ -816,6 +960,96 @@ mod test {
         assert_eq!(code, StatusCode::NOT_FOUND);
     }
 
+    #[tokio::test]
+    async fn questions_hidden_before_start() {
+        setup_test_logger();
+
+        let state = mock_state! {
+            packet: double_problem_packet(),
+        };
+
+        let code = get_all(user!("foobar", Competitor).into(), State(state.clone()))
+            .await
+            .unwrap_err();
+        assert_eq!(code, StatusCode::CONFLICT);
+        let code = get_specific_question(
+            State(state.clone()),
+            user!("foobar", Competitor).into(),
+            axum::extract::Path(0),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(code, StatusCode::CONFLICT);
+
+        let Json(value) = get_all(user!("host", Host).into(), State(state.clone()))
+            .await
+            .unwrap();
+        assert_eq!(value.len(), 2);
+
+        state.clock.write().await.unpause();
+        let Json(value) = get_all(user!("foobar", Competitor).into(), State(state))
+            .await
+            .unwrap();
+        assert_eq!(value.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn unreleased_questions_are_hidden() {
+        setup_test_logger();
+
+        let mut state = mock_state! {
+            packet: double_problem_packet(),
+        };
+        Arc::get_mut(&mut state)
+            .unwrap()
+            .settings
+            .problems
+            .push(ProblemSettings {
+                index: 1,
+                manual_release: true,
+                ..Default::default()
+            });
+        state.clock.write().await.unpause();
+
+        let Json(value) = get_all(user!("foobar", Competitor).into(), State(state.clone()))
+            .await
+            .unwrap();
+        assert_eq!(value.iter().map(|q| q.index).collect::<Vec<_>>(), [0]);
+        let Json(value) = get_all(user!("host", Host).into(), State(state.clone()))
+            .await
+            .unwrap();
+        assert_eq!(value.len(), 2);
+
+        let code = get_specific_question(
+            State(state.clone()),
+            user!("foobar", Competitor).into(),
+            axum::extract::Path(1),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(code, StatusCode::NOT_FOUND);
+
+        let host = user!("host", Host);
+        let Json(released) = release_question(
+            State(state.clone()),
+            HostUser(host.clone()),
+            axum::extract::Path(1),
+        )
+        .await
+        .unwrap();
+        assert!(released);
+        let Json(released) =
+            release_question(State(state.clone()), HostUser(host), axum::extract::Path(1))
+                .await
+                .unwrap();
+        assert!(!released);
+
+        let Json(value) = get_all(user!("foobar", Competitor).into(), State(state))
+            .await
+            .unwrap();
+        assert_eq!(value.len(), 2);
+    }
+
     #[tokio::test]
     async fn create_submission_valid() {
         setup_test_logger();
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, Json};
+use tracing::{error, info};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    extractors::auth::HostUser,
+    repositories::submissions::SubmissionId,
+    server::{
+        tester::{self, Rejudge},
+        AppState,
+    },
+};
+
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/", tag = "rejudge", request_body = Rejudge,
+    description = "Judge submissions again against the current tests, replacing their results and scores.  Submissions are judged one at a time in the order in which they were made, and teams are notified of their new scores as each finishes.",
+    responses(
+        (status = ACCEPTED, body = Vec<SubmissionId>, description = "The submissions which will be judged again"),
+        (status = 403, description = "User does not have permission to rejudge submissions"),
+        (status = 409, description = "Some of the submissions are already being judged again"),
+    )
+)]
+async fn rejudge(
+    State(state): State<Arc<AppState>>,
+    HostUser(host): HostUser,
+    Json(body): Json<Rejudge>,
+) -> Result<(StatusCode, Json<Vec<SubmissionId>>), StatusCode> {
+    let submissions = body.submissions(&state.db).await.map_err(|error| {
+        error!(?error, "Error getting submissions to rejudge");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let ids: Vec<_> = submissions.iter().map(|s| s.id).collect();
+    // judging a submission again while an earlier rejudge of it is still running could leave
+    // either result in place, so the submissions are claimed before anything is judged
+    let claimed: Vec<_> = ids
+        .iter()
+        .take_while(|id| state.rejudging.insert(**id))
+        .collect();
+    if claimed.len() < ids.len() {
+        for id in claimed {
+            state.rejudging.remove(id);
+        }
+        return Err(StatusCode::CONFLICT);
+    }
+
+    info!(host = %host.username, count = ids.len(), ?body, "Rejudging submissions");
+    let claimed = ids.clone();
+    tokio::spawn(async move {
+        let rejudged = tester::rejudge_all(Arc::clone(&state), submissions).await;
+        for id in &claimed {
+            state.rejudging.remove(id);
+        }
+        info!(count = rejudged.len(), "Finished rejudging submissions");
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(ids)))
+}
+
+pub fn router() -> OpenApiRouter<Arc<AppState>> {
+    OpenApiRouter::new().routes(routes!(rejudge))
+}
+
+pub fn service() -> axum::Router<Arc<AppState>> {
+    router().split_for_parts().0
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use tracing::{error, info};
+use utoipa::ToSchema;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    extractors::auth::HostUser,
+    server::{
+        resolver::{self, Reveal},
+        AppState,
+    },
+};
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ResolverStatus {
+    /// Whether the scoreboard is frozen
+    frozen: bool,
+    /// Number of results which are hidden by the freeze
+    hidden: usize,
+    /// Number of submissions made during the freeze which are still being judged
+    judging: usize,
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/", tag = "resolver",
+    responses(
+        (status = OK, body = ResolverStatus, description = "Number of results that are still hidden"),
+        (status = 403, description = "User does not have permission to use the resolver"),
+    )
+)]
+async fn get_status(
+    State(state): State<Arc<AppState>>,
+    HostUser(_): HostUser,
+) -> Result<Json<ResolverStatus>, StatusCode> {
+    let hidden = resolver::hidden(&state).await.map_err(|error| {
+        error!(?error, "Error getting hidden results");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let judging = resolver::judging(&state).await.map_err(|error| {
+        error!(?error, "Error counting submissions being judged");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(ResolverStatus {
+        frozen: hidden.is_some(),
+        hidden: hidden.unwrap_or_default(),
+        judging,
+    }))
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/step", tag = "resolver",
+    description = "Reveal the next result hidden by the scoreboard freeze.  Leaderboards are notified of each result as it is revealed, and the scoreboard is unfrozen once nothing is hidden.",
+    responses(
+        (status = OK, body = Reveal, description = "The result that was revealed"),
+        (status = NO_CONTENT, description = "No results are hidden"),
+        (status = 403, description = "User does not have permission to use the resolver"),
+        (status = 409, description = "Competition is not over yet, or submissions made during the freeze are still being judged"),
+    )
+)]
+async fn step(
+    State(state): State<Arc<AppState>>,
+    HostUser(host): HostUser,
+) -> Result<(StatusCode, Json<Option<Reveal>>), StatusCode> {
+    if !state.is_game_over().await {
+        return Err(StatusCode::CONFLICT);
+    }
+    // revealing now would skip results that are not known yet, and could unfreeze the scoreboard
+    // before they are
+    let judging = resolver::judging(&state).await.map_err(|error| {
+        error!(?error, "Error counting submissions being judged");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if judging > 0 {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let reveal = resolver::step(&state).await.map_err(|error| {
+        error!(?error, "Error revealing result");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    match reveal {
+        Some(reveal) => {
+            info!(host = %host.username, submission = %reveal.submission, "Revealed result");
+            Ok((StatusCode::OK, Json(Some(reveal))))
+        }
+        None => Ok((StatusCode::NO_CONTENT, Json(None))),
+    }
+}
+
+pub fn router() -> OpenApiRouter<Arc<AppState>> {
+    OpenApiRouter::new()
+        .routes(routes!(get_status))
+        .routes(routes!(step))
+}
+
+pub fn service() -> axum::Router<Arc<AppState>> {
+    router().split_for_parts().0
+}
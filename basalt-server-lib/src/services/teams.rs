@@ -1,30 +1,31 @@
 use std::sync::Arc;
 
-use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
 use axum::{
     extract::{Path, State},
     http::StatusCode,
     Json,
 };
-use rand::rngs::OsRng;
+use chrono::TimeDelta;
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
 use tokio::task::JoinSet;
 use tracing::{error, info, trace};
 use utoipa::ToSchema;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 use crate::{
-    extractors::auth::HostUser,
+    extractors::auth::{HostUser, OptionalUser},
     repositories::{
         self,
-        submissions::get_user_score,
-        users::{get_user_by_id, GetUserError, QuestionState, User, UserId},
+        users::{get_user_by_id, GetUserError, QuestionState, Role, User, UserId},
     },
     server::{
+        standings,
         teams::{TeamFull, TeamWithScore},
+        websocket::ConnectionKind,
         AppState,
     },
-    services::ws::{Broadcast, TeamUpdate},
+    services::ws::{Broadcast, TeamUpdate, WebSocketSend},
     utils::OneOrMany,
 };
 
@@ -42,16 +43,22 @@ struct TeamsListResponse(Vec<TeamWithScore>);
     )
 )]
 async fn get_teams(
+    OptionalUser(viewer): OptionalUser,
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<TeamsListResponse>, StatusCode> {
     trace!("user getting teams info");
+    let cutoff = state.scoreboard_cutoff(viewer.as_ref()).await;
     let teams = state.team_manager.list();
     let mut joinset = JoinSet::new();
     for t in teams {
         let state = Arc::clone(&state);
-        async fn fut(t: TeamFull, state: Arc<AppState>) -> anyhow::Result<TeamWithScore> {
+        async fn fut(
+            t: TeamFull,
+            state: Arc<AppState>,
+            cutoff: Option<OffsetDateTime>,
+        ) -> anyhow::Result<TeamWithScore> {
             let user = get_user_by_id(&state.db, &t.id).await?;
-            let score = get_user_score(&state.db, &t.id).await?;
+            let score = standings::get_score(&state.db, &state.config.game, &t.id, cutoff).await?;
             Ok(TeamWithScore {
                 team_info: t,
                 id: user.id,
@@ -60,7 +67,7 @@ async fn get_teams(
                 score,
             })
         }
-        joinset.spawn(fut(t, state));
+        joinset.spawn(fut(t, state, cutoff));
     }
     joinset
         .join_all()
@@ -223,12 +230,7 @@ async fn patch_team(
     }
 
     if let Some(password) = patch.password {
-        let salt = SaltString::generate(&mut OsRng);
-        let password_hash = Argon2::default()
-            .hash_password(password.as_bytes(), &salt)
-            .expect("Failed to hash password")
-            .to_string();
-        user.password_hash = password_hash.into();
+        user.password_hash = repositories::users::hash_password(password);
     }
 
     let new = repositories::users::update_user(&state.db, user)
@@ -247,11 +249,99 @@ async fn patch_team(
     Ok(Json(new))
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct TeamTimeRequest {
+    /// Seconds given to the team on top of the time limit, such as for accessibility
+    /// accommodations or a late start.  Negative values remove time and `0` removes the offset.
+    offset_in_seconds: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct TeamTimeResponse {
+    id: UserId,
+    offset_in_seconds: i64,
+    time_left_in_seconds: u64,
+}
+
+#[axum::debug_handler]
+#[utoipa::path(
+    put,
+    path="/{id}/time", tag="teams",
+    request_body = TeamTimeRequest,
+    responses(
+        (status=OK, body=TeamTimeResponse, description="Time given to the team was updated"),
+        (status=BAD_REQUEST, description="Offset is out of range"),
+        (status=NOT_FOUND, description="Team with ID not found"),
+        (status=INTERNAL_SERVER_ERROR),
+    )
+)]
+async fn put_team_time(
+    State(state): State<Arc<AppState>>,
+    HostUser(host): HostUser,
+    Path(user_id): Path<UserId>,
+    Json(body): Json<TeamTimeRequest>,
+) -> Result<Json<TeamTimeResponse>, StatusCode> {
+    info!(host = %host.username, %user_id, offset = body.offset_in_seconds, "Setting team time offset");
+    let offset = TimeDelta::try_seconds(body.offset_in_seconds).ok_or(StatusCode::BAD_REQUEST)?;
+
+    let user = repositories::users::get_user_by_id(&state.db, &user_id)
+        .await
+        .map_err(|e| match e {
+            GetUserError::QueryError(_) => {
+                error!("Error getting user: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            GetUserError::UserNotFound { .. } => StatusCode::NOT_FOUND,
+        })?;
+    if user.role != Role::Competitor {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let time_left_in_seconds = {
+        let mut clock = state.clock.write().await;
+        repositories::clock::set_team_offset(&state.db, &user_id, offset)
+            .await
+            .map_err(|e| {
+                error!("Error saving team time offset: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+        clock.set_team_offset(user_id, offset);
+        state.broadcast_clock(&clock);
+
+        clock
+            .current_team_time(&user_id)
+            .map_err(|e| {
+                error!("Error getting team time: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+            .time_left(state.time_limit())
+            .as_secs()
+    };
+
+    if let Some(conn) = state
+        .websocket
+        .get_sender(&ConnectionKind::User { user: user_id })
+    {
+        let _ = conn.send(WebSocketSend::TeamTimeAdjusted {
+            time_left_in_seconds,
+        });
+    }
+
+    Ok(Json(TeamTimeResponse {
+        id: user_id,
+        offset_in_seconds: body.offset_in_seconds,
+        time_left_in_seconds,
+    }))
+}
+
 pub fn router() -> OpenApiRouter<Arc<AppState>> {
     OpenApiRouter::new()
         .routes(routes!(get_teams))
         .routes(routes!(add_team))
         .routes(routes!(patch_team))
+        .routes(routes!(put_team_time))
 }
 
 pub fn service() -> axum::Router<Arc<AppState>> {
@@ -293,7 +383,10 @@ mod tests {
 
         let mut appstate = AppState::new(db, cfg, None);
         appstate.init().await.unwrap();
-        let Json(TeamsListResponse(teams)) = get_teams(State(Arc::new(appstate))).await.unwrap();
+        let Json(TeamsListResponse(teams)) =
+            get_teams(OptionalUser(None), State(Arc::new(appstate)))
+                .await
+                .unwrap();
 
         assert_eq!(
             teams
@@ -304,4 +397,64 @@ mod tests {
             expected_score
         );
     }
+
+    #[tokio::test]
+    async fn team_time_is_saved() {
+        let state = crate::mock_state! {};
+        let host = crate::testing::db_user(&state.db, "host", Role::Host).await;
+        let team = crate::testing::db_user(&state.db, "team", Role::Competitor).await;
+        let other = crate::testing::db_user(&state.db, "other", Role::Competitor).await;
+        let mut team_rx = state
+            .websocket
+            .add_connection(ConnectionKind::User { user: team.id });
+        let mut other_rx = state
+            .websocket
+            .add_connection(ConnectionKind::User { user: other.id });
+
+        let Json(response) = put_team_time(
+            State(Arc::clone(&state)),
+            HostUser(host.clone()),
+            Path(team.id),
+            Json(TeamTimeRequest {
+                offset_in_seconds: 600,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            response.time_left_in_seconds,
+            state.time_limit().as_secs() + 600
+        );
+
+        // the clock is synced straight away, and only the team is given more time
+        let synced = |rx: &mut tokio::sync::mpsc::UnboundedReceiver<WebSocketSend>| {
+            std::iter::from_fn(|| rx.try_recv().ok()).find_map(|message| match message {
+                WebSocketSend::Broadcast(Broadcast::Clock {
+                    time_left_in_seconds,
+                    ..
+                }) => Some(time_left_in_seconds),
+                _ => None,
+            })
+        };
+        assert_eq!(synced(&mut team_rx), Some(response.time_left_in_seconds));
+        assert_eq!(synced(&mut other_rx), Some(state.time_limit().as_secs()));
+
+        let saved = repositories::clock::get_clock(&state.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.team_offset(&team.id), TimeDelta::minutes(10));
+        assert_eq!(*state.clock.read().await, saved);
+
+        let result = put_team_time(
+            State(Arc::clone(&state)),
+            HostUser(host.clone()),
+            Path(host.id),
+            Json(TeamTimeRequest {
+                offset_in_seconds: 600,
+            }),
+        )
+        .await;
+        assert_eq!(result.unwrap_err(), StatusCode::NOT_FOUND);
+    }
 }
@@ -85,6 +85,11 @@ async fn handle_socket(
 
     loop {
         tokio::select! {
+            _ = state.shutdown.cancelled() => {
+                trace!("Server shutting down, closing connection");
+                let _ = ws.send(Message::Close(None)).await;
+                return Ok(());
+            },
             msg = rx.recv() => match msg {
                 None => {
                     // Connection is closed.
@@ -1,4 +1,4 @@
-use erudite::runner::TestResult;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use utoipa_axum::{router::OpenApiRouter, routes};
@@ -11,7 +11,8 @@ use crate::{
         },
         users::{QuestionState, UserId},
     },
-    server::{teams::TeamWithScore, tester::TestData, websocket::ConnectionKind, AppState},
+    server::{resolver::Reveal, teams::TeamWithScore, websocket::ConnectionKind, AppState},
+    services::questions::QuestionResponse,
 };
 
 pub mod connect;
@@ -37,11 +38,40 @@ pub enum Broadcast {
         message: String,
     },
     GamePaused,
+    /// The competition has started for the first time, so the packet can be fetched
+    GameStarted {
+        /// Questions which have been released, without their hidden tests
+        #[serde(skip_deserializing)]
+        questions: Vec<&'static QuestionResponse>,
+    },
     TeamConnected(TeamWithScore),
     TeamDisconnected(TeamWithScore),
     GameUnpaused {
         time_left_in_seconds: u64,
     },
+    /// State of the clock, sent periodically and whenever the clock changes so that countdowns stay
+    /// in sync with the server
+    Clock {
+        is_paused: bool,
+        time_left_in_seconds: u64,
+        server_time: DateTime<Utc>,
+    },
+    /// A host has added time to or removed time from the competition
+    TimeAdjusted {
+        time_left_in_seconds: u64,
+    },
+    /// A problem which was hidden has been released, and the questions should be fetched again
+    QuestionReleased {
+        index: usize,
+    },
+    /// The time limit has run out and no more submissions are accepted
+    GameOver,
+    /// Scores are no longer updated for anybody except hosts until the scoreboard is unfrozen
+    ScoreboardFrozen,
+    /// Scores are live again, and should be fetched again
+    ScoreboardUnfrozen,
+    /// The resolver has revealed a result that was hidden by the scoreboard freeze
+    ResultRevealed(Reveal),
     TeamRename {
         id: UserId,
         name: String,
@@ -77,19 +107,6 @@ impl From<DbTestResults> for TestResultSend {
     }
 }
 
-impl From<&TestResult<TestData>> for TestResultSend {
-    fn from(value: &TestResult<TestData>) -> Self {
-        Self {
-            index: value.index(),
-            state: value.state().into(),
-            stdout: value.stdout().to_str_lossy().into_owned(),
-            stderr: value.stderr().to_str_lossy().into_owned(),
-            exit_status: value.exit_status(),
-            time_taken: value.time_taken().as_millis() as u64,
-        }
-    }
-}
-
 #[derive(Clone, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SubmissionResultSend {
@@ -109,16 +126,6 @@ impl From<DbTestResults> for SubmissionResultSend {
     }
 }
 
-impl From<&TestResult<TestData>> for SubmissionResultSend {
-    fn from(value: &TestResult<TestData>) -> Self {
-        Self {
-            index: value.index(),
-            state: value.state().into(),
-            time_taken: value.time_taken().as_millis() as u64,
-        }
-    }
-}
-
 #[derive(Clone, Debug, Serialize)]
 #[serde(untagged)]
 pub enum Results {
@@ -135,7 +142,7 @@ impl Results {
         Self::Submission(tests.into_iter().map(Into::into).collect())
     }
 
-    pub fn push(&mut self, test: &TestResult<TestData>) {
+    pub fn push(&mut self, test: DbTestResults) {
         match self {
             Results::Test(x) => x.push(test.into()),
             Results::Submission(x) => x.push(test.into()),
@@ -199,6 +206,8 @@ pub enum WebSocketSend {
         results: Results,
         remaining_attempts: Option<u32>,
     },
+    /// A host has changed the time given to this team on top of the time limit
+    TeamTimeAdjusted { time_left_in_seconds: u64 },
     #[serde(untagged)]
     Broadcast(Broadcast),
 }
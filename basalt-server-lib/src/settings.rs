@@ -0,0 +1,500 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{bail, Context};
+use bedrock::Config;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::server::{checker::Checker, interactive::Interactor};
+
+/// Settings for a competition which are specific to the server and are not part of the packet
+/// configuration.
+///
+/// These are read from a separate TOML file, see `samples/settings.toml`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Settings for individual problems
+    pub problems: Vec<ProblemSettings>,
+    /// Settings which only apply in race mode
+    pub race: RaceSettings,
+    /// When the competition starts
+    pub schedule: ScheduleSettings,
+    /// Settings for individual languages, keyed by their raw name
+    pub languages: HashMap<String, LanguageSettings>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LanguageSettings {
+    /// Factor by which the time limit of every problem is multiplied for this language
+    pub time_multiplier: f64,
+    /// Factor by which the memory limit of every problem is multiplied for this language
+    pub memory_multiplier: f64,
+}
+
+impl Default for LanguageSettings {
+    fn default() -> Self {
+        Self {
+            time_multiplier: 1.,
+            memory_multiplier: 1.,
+        }
+    }
+}
+
+/// Time and memory limits of each test of a problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProblemLimits {
+    pub time: Duration,
+    /// Maximum memory in MiB, see [`Limits`](crate::server::limits::Limits) for how it is enforced
+    pub memory: u64,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScheduleSettings {
+    /// Time at which the clock is started automatically, as an RFC 3339 timestamp.  If omitted, a
+    /// host must start the competition by unpausing the clock.
+    ///
+    /// The competition always ends once the time limit has run out.
+    pub start: Option<DateTime<Utc>>,
+    /// Number of minutes before the end of the competition at which the scoreboard is frozen.
+    /// While frozen, only hosts see scores change.
+    pub freeze: Option<u64>,
+}
+
+impl ScheduleSettings {
+    pub fn freeze(&self) -> Option<Duration> {
+        self.freeze.map(|minutes| Duration::from_secs(minutes * 60))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RaceSettings {
+    /// Length of the competition in minutes.  Race packets do not specify a time limit.
+    pub time_limit: u64,
+}
+
+impl Default for RaceSettings {
+    fn default() -> Self {
+        Self { time_limit: 75 }
+    }
+}
+
+impl RaceSettings {
+    pub fn time_limit(&self) -> Duration {
+        Duration::from_secs(self.time_limit * 60)
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProblemSettings {
+    /// Index of the problem in `packet.problems` to which these settings apply
+    pub index: usize,
+    /// Title of the problem, which must match the packet if it is given so that settings are not
+    /// applied to the wrong problem once the packet has been reordered
+    pub title: Option<String>,
+    /// Reference solutions for this problem, mapping the raw name of a language to a source
+    /// file relative to the settings file.  These are only used by the `check` command.
+    #[serde(default)]
+    pub solutions: HashMap<String, PathBuf>,
+    /// Number of minutes into the competition after which this problem is released.  Problems
+    /// without a release time or `manual_release` are available from the start.
+    pub release_after: Option<u64>,
+    /// Keep this problem hidden until a host releases it
+    #[serde(default)]
+    pub manual_release: bool,
+    /// How the output of solutions is compared with the expected output of each test
+    #[serde(default)]
+    pub checker: Checker,
+    /// Time limit of each test in seconds, instead of `timeout` in the `[test_runner]` section of
+    /// the configuration
+    pub time_limit: Option<f64>,
+    /// Memory limit of each test in MiB, instead of `max_memory.run` in the `[test_runner]`
+    /// section of the configuration
+    pub memory_limit: Option<u64>,
+    /// Program which talks to solutions and decides whether they are correct, making this an
+    /// interactive problem
+    pub interactor: Option<Interactor>,
+}
+
+impl ProblemSettings {
+    pub fn release_after(&self) -> Option<Duration> {
+        self.release_after
+            .map(|minutes| Duration::from_secs(minutes * 60))
+    }
+
+    /// Whether this problem is hidden until it is released, either on a schedule or by a host
+    pub fn is_scheduled(&self) -> bool {
+        self.release_after.is_some() || self.manual_release
+    }
+}
+
+impl Settings {
+    /// Read settings from a TOML file.  Relative paths in the settings are resolved against the
+    /// directory containing the file.
+    pub async fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Reading settings file {}", path.display()))?;
+        let mut settings: Self = content
+            .parse()
+            .with_context(|| format!("Parsing {}", path.display()))?;
+
+        let path = tokio::fs::canonicalize(path)
+            .await
+            .with_context(|| format!("Resolving {}", path.display()))?;
+        if let Some(dir) = path.parent() {
+            settings.resolve_paths(dir);
+        }
+        Ok(settings)
+    }
+
+    /// Make the paths of solutions relative to `dir`, and run checker and interactor programs
+    /// from it
+    fn resolve_paths(&mut self, dir: &Path) {
+        for problem in &mut self.problems {
+            for path in problem.solutions.values_mut() {
+                *path = dir.join(&*path);
+            }
+            if let Checker::Program {
+                dir: program_dir, ..
+            } = &mut problem.checker
+            {
+                *program_dir = Some(dir.to_path_buf());
+            }
+            if let Some(interactor) = &mut problem.interactor {
+                interactor.dir = Some(dir.to_path_buf());
+            }
+        }
+    }
+
+    /// Get the settings for the problem at `index`, if any are specified
+    pub fn problem(&self, index: usize) -> Option<&ProblemSettings> {
+        self.problems.iter().find(|p| p.index == index)
+    }
+
+    /// Limits of each test of the problem at `index` when it is solved in `language`, or before
+    /// they are multiplied for any language if `language` is `None`
+    pub fn limits(&self, config: &Config, index: usize, language: Option<&str>) -> ProblemLimits {
+        let problem = self.problem(index);
+        let time = problem
+            .and_then(|p| p.time_limit)
+            .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+            .unwrap_or(config.test_runner.timeout);
+        let memory = problem
+            .and_then(|p| p.memory_limit)
+            .unwrap_or(config.test_runner.max_memory.run);
+
+        match language.and_then(|l| self.languages.get(l)) {
+            Some(language) => ProblemLimits {
+                time: Duration::try_from_secs_f64(time.as_secs_f64() * language.time_multiplier)
+                    .unwrap_or(time),
+                memory: (memory as f64 * language.memory_multiplier).round() as u64,
+            },
+            None => ProblemLimits { time, memory },
+        }
+    }
+
+    /// Ensure that these settings refer to problems and languages that exist in `config`
+    pub fn validate(&self, config: &Config) -> anyhow::Result<()> {
+        let mut seen = HashSet::new();
+        for problem in &self.problems {
+            if problem.index >= config.packet.problems.len() {
+                bail!(
+                    "Settings refer to problem {}, but the packet only has {} problems",
+                    problem.index,
+                    config.packet.problems.len()
+                );
+            }
+            if !seen.insert(problem.index) {
+                bail!("Problem {} is specified more than once", problem.index);
+            }
+            let title = &config.packet.problems[problem.index].title;
+            match &problem.title {
+                Some(expected) if expected != title => bail!(
+                    "Settings for problem {} are titled '{}', but the problem is titled '{}'",
+                    problem.index,
+                    expected,
+                    title
+                ),
+                _ => {}
+            }
+            if problem.release_after.is_some() && problem.manual_release {
+                bail!(
+                    "Problem {} cannot be released both on a schedule and manually",
+                    problem.index
+                );
+            }
+            match &problem.checker {
+                Checker::Float { tolerance } if !tolerance.is_finite() || *tolerance < 0. => {
+                    bail!(
+                        "Checker of problem {} has an invalid tolerance {}",
+                        problem.index,
+                        tolerance
+                    );
+                }
+                Checker::Program { command, .. } if command.trim().is_empty() => {
+                    bail!("Checker program of problem {} is empty", problem.index);
+                }
+                _ => {}
+            }
+            if problem
+                .time_limit
+                .is_some_and(|seconds| !seconds.is_finite() || seconds <= 0.)
+                || problem.memory_limit == Some(0)
+            {
+                bail!("Problem {} has an invalid limit", problem.index);
+            }
+            if let Some(interactor) = &problem.interactor {
+                if interactor.command.trim().is_empty() {
+                    bail!("Interactor of problem {} is empty", problem.index);
+                }
+                if !problem.checker.is_exact() {
+                    bail!(
+                        "Problem {} cannot have both an interactor and a checker",
+                        problem.index
+                    );
+                }
+            }
+            for language in problem.solutions.keys() {
+                if config.languages.get_by_str(language).is_none() {
+                    bail!(
+                        "Solution for problem {} uses unknown language '{}'",
+                        problem.index,
+                        language
+                    );
+                }
+            }
+        }
+        for (name, language) in &self.languages {
+            if config.languages.get_by_str(name).is_none() {
+                bail!("Settings refer to unknown language '{}'", name);
+            }
+            let valid = |multiplier: f64| multiplier.is_finite() && multiplier > 0.;
+            if !valid(language.time_multiplier) || !valid(language.memory_multiplier) {
+                bail!("Language '{}' has an invalid multiplier", name);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Settings {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sample() {
+        let settings = include_str!("../../samples/settings.toml")
+            .parse::<Settings>()
+            .unwrap();
+        let problem = settings.problem(0).unwrap();
+        assert!(problem.solutions.contains_key("python3"));
+        assert!(settings.problem(1).is_none());
+    }
+
+    #[test]
+    fn empty_settings() {
+        let settings = "".parse::<Settings>().unwrap();
+        assert!(settings.problems.is_empty());
+        assert_eq!(settings.race.time_limit(), Duration::from_secs(75 * 60));
+        assert!(settings.schedule.start.is_none());
+        assert!(settings.schedule.freeze().is_none());
+    }
+
+    #[test]
+    fn scheduled_start() {
+        let settings = r#"
+            [schedule]
+            start = "2025-04-12T09:30:00-05:00"
+            freeze = 60
+        "#
+        .parse::<Settings>()
+        .unwrap();
+        assert_eq!(
+            settings.schedule.start,
+            Some("2025-04-12T14:30:00Z".parse().unwrap())
+        );
+        assert_eq!(
+            settings.schedule.freeze(),
+            Some(Duration::from_secs(60 * 60))
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!("foo = 1".parse::<Settings>().is_err());
+    }
+
+    #[test]
+    fn validate_problem_index() {
+        let cfg = Config::from_str(crate::testing::SAMPLE_1, Some("single.toml")).unwrap();
+        let settings = r#"
+            [[problems]]
+            index = 5
+        "#
+        .parse::<Settings>()
+        .unwrap();
+        assert!(settings.validate(&cfg).is_err());
+
+        let settings = r#"
+            [[problems]]
+            index = 0
+            title = "Reversing a number"
+        "#
+        .parse::<Settings>()
+        .unwrap();
+        assert!(settings.validate(&cfg).is_err());
+
+        let settings = include_str!("../../samples/settings.toml")
+            .parse::<Settings>()
+            .unwrap();
+        settings.validate(&cfg).unwrap();
+    }
+
+    #[test]
+    fn validate_interactor() {
+        let cfg = Config::from_str(crate::testing::SAMPLE_1, Some("single.toml")).unwrap();
+        let settings = r#"
+            [[problems]]
+            index = 0
+            interactor = { command = "python3 ./interact.py \"$@\"" }
+            checker = { kind = "whitespace" }
+        "#
+        .parse::<Settings>()
+        .unwrap();
+        assert!(settings.validate(&cfg).is_err());
+
+        let settings = r#"
+            [[problems]]
+            index = 0
+            interactor = { command = "python3 ./interact.py \"$@\"" }
+        "#
+        .parse::<Settings>()
+        .unwrap();
+        settings.validate(&cfg).unwrap();
+        assert!(settings.problem(0).unwrap().interactor.is_some());
+    }
+
+    #[test]
+    fn limits() {
+        let cfg = Config::from_str(crate::testing::SAMPLE_1, Some("single.toml")).unwrap();
+        let settings = r#"
+            [languages.java]
+            time_multiplier = 2
+            memory_multiplier = 1.5
+
+            [[problems]]
+            index = 0
+            time_limit = 2.5
+            memory_limit = 256
+        "#
+        .parse::<Settings>()
+        .unwrap();
+        settings.validate(&cfg).unwrap();
+
+        assert_eq!(
+            settings.limits(&cfg, 0, None),
+            ProblemLimits {
+                time: Duration::from_millis(2500),
+                memory: 256,
+            }
+        );
+        assert_eq!(
+            settings.limits(&cfg, 0, Some("java")),
+            ProblemLimits {
+                time: Duration::from_secs(5),
+                memory: 384,
+            }
+        );
+        assert_eq!(
+            settings.limits(&cfg, 0, Some("python3")),
+            settings.limits(&cfg, 0, None)
+        );
+
+        let settings = r#"
+            [languages.cobol]
+            time_multiplier = 2
+        "#
+        .parse::<Settings>()
+        .unwrap();
+        assert!(settings.validate(&cfg).is_err());
+    }
+
+    #[test]
+    fn resolve_paths() {
+        let mut settings = r#"
+            [[problems]]
+            index = 0
+            solutions = { python3 = "solutions/reverse.py", java = "/abs/Reverse.java" }
+            interactor = { command = "./interact.sh" }
+        "#
+        .parse::<Settings>()
+        .unwrap();
+        assert_eq!(
+            settings
+                .problem(0)
+                .unwrap()
+                .interactor
+                .as_ref()
+                .unwrap()
+                .dir,
+            None
+        );
+
+        settings.resolve_paths(Path::new("/competition"));
+        let problem = settings.problem(0).unwrap();
+        assert_eq!(
+            problem.solutions["python3"],
+            Path::new("/competition/solutions/reverse.py")
+        );
+        assert_eq!(problem.solutions["java"], Path::new("/abs/Reverse.java"));
+        assert_eq!(
+            problem.interactor.as_ref().unwrap().dir.as_deref(),
+            Some(Path::new("/competition"))
+        );
+    }
+
+    #[test]
+    fn validate_release() {
+        let cfg = Config::from_str(crate::testing::SAMPLE_1, Some("single.toml")).unwrap();
+        let settings = r#"
+            [[problems]]
+            index = 0
+            release_after = 30
+            manual_release = true
+        "#
+        .parse::<Settings>()
+        .unwrap();
+        assert!(settings.validate(&cfg).is_err());
+
+        let settings = r#"
+            [[problems]]
+            index = 0
+            release_after = 30
+        "#
+        .parse::<Settings>()
+        .unwrap();
+        settings.validate(&cfg).unwrap();
+        let problem = settings.problem(0).unwrap();
+        assert!(problem.is_scheduled());
+        assert_eq!(problem.release_after(), Some(Duration::from_secs(30 * 60)));
+    }
+}
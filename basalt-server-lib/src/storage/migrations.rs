@@ -0,0 +1,161 @@
+use anyhow::{bail, Context};
+use sqlx::{SqliteExecutor, SqlitePool};
+use tracing::info;
+
+/// A change to the database schema
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    sql: &'static str,
+}
+
+/// All migrations, in the order in which they are applied.
+///
+/// This is generated by the build script from the files in the `migrations` directory, whose names
+/// start with the version of the migration.  New migrations are added by adding a file with the
+/// next version, and existing migrations must never be changed once released.
+pub const MIGRATIONS: &[Migration] = include!(concat!(env!("OUT_DIR"), "/migrations.rs"));
+
+/// Version of the schema once all migrations have been applied
+pub const SCHEMA_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// Check whether a database has a table, which may be missing from databases that were opened
+/// without applying migrations
+pub async fn table_exists(db: impl SqliteExecutor<'_>, name: &str) -> anyhow::Result<bool> {
+    sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+    )
+    .bind(name)
+    .fetch_one(db)
+    .await
+    .with_context(|| format!("Checking for table {}", name))
+}
+
+/// Get the schema version of a database, `0` if no migrations have been applied
+pub async fn schema_version(db: &SqlitePool) -> anyhow::Result<i64> {
+    if !table_exists(db, "schema_version").await? {
+        return Ok(0);
+    }
+
+    let version: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
+        .fetch_one(db)
+        .await
+        .context("Getting schema version")?;
+    Ok(version.unwrap_or_default())
+}
+
+/// Apply all migrations which have not yet been applied to the database.
+///
+/// Fails if the database was created by a newer version of the server, since it may rely on
+/// changes that this version does not know about.
+pub async fn migrate(db: &SqlitePool) -> anyhow::Result<()> {
+    let current = schema_version(db).await?;
+    if current > SCHEMA_VERSION {
+        bail!(
+            "Database schema version {} is newer than the latest version supported by this server ({}), upgrade basalt-server to use this database",
+            current,
+            SCHEMA_VERSION
+        );
+    }
+
+    sqlx::raw_sql(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER NOT NULL PRIMARY KEY,
+            applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+    )
+    .execute(db)
+    .await
+    .context("Creating schema version table")?;
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        info!(
+            version = migration.version,
+            name = migration.name,
+            "Applying database migration"
+        );
+        let mut tx = db.begin().await.context("Starting migration transaction")?;
+        sqlx::raw_sql(migration.sql)
+            .execute(&mut *tx)
+            .await
+            .with_context(|| format!("Applying migration {}", migration.name))?;
+        sqlx::query("INSERT INTO schema_version (version) VALUES (?)")
+            .bind(migration.version)
+            .execute(&mut *tx)
+            .await
+            .context("Updating schema version")?;
+        tx.commit()
+            .await
+            .with_context(|| format!("Committing migration {}", migration.name))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqliteConnectOptions;
+    use std::str::FromStr;
+
+    use super::*;
+
+    async fn empty_db() -> SqlitePool {
+        SqlitePool::connect_with(SqliteConnectOptions::from_str("sqlite::memory:").unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn versions_are_sequential() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i64 + 1);
+            assert!(migration
+                .name
+                .starts_with(&format!("{:04}_", migration.version)));
+        }
+    }
+
+    #[tokio::test]
+    async fn migrate_fresh_database() {
+        let db = empty_db().await;
+        assert_eq!(schema_version(&db).await.unwrap(), 0);
+        migrate(&db).await.unwrap();
+        assert_eq!(schema_version(&db).await.unwrap(), SCHEMA_VERSION);
+
+        // running again is a no-op
+        migrate(&db).await.unwrap();
+        assert_eq!(schema_version(&db).await.unwrap(), SCHEMA_VERSION);
+    }
+
+    #[tokio::test]
+    async fn migrate_unversioned_database() {
+        let db = empty_db().await;
+        sqlx::raw_sql(MIGRATIONS[0].sql).execute(&db).await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, password_hash) VALUES ('a', 'foo', '')")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        migrate(&db).await.unwrap();
+        assert_eq!(schema_version(&db).await.unwrap(), SCHEMA_VERSION);
+        let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(users, 1);
+    }
+
+    #[tokio::test]
+    async fn refuse_newer_database() {
+        let db = empty_db().await;
+        migrate(&db).await.unwrap();
+        sqlx::query("INSERT INTO schema_version (version) VALUES (?)")
+            .bind(SCHEMA_VERSION + 1)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let err = migrate(&db).await.unwrap_err();
+        assert!(err.to_string().contains("newer"));
+    }
+}
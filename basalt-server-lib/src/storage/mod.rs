@@ -1,8 +1,13 @@
 use anyhow::Context;
 use bedrock::Config;
+use chrono::Utc;
 use derive_more::Deref;
 use futures::{future::BoxFuture, stream::BoxStream};
-use std::{path::Path, str::FromStr};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 use tokio::fs::File;
 use tracing::debug;
 
@@ -11,11 +16,35 @@ use sqlx::{
     Database, Executor, Pool, Sqlite,
 };
 
-use crate::repositories::users::{create_user, Role};
+pub mod migrations;
+
+use crate::repositories::{
+    competition::{self, Competition},
+    session,
+    users::{self, create_user, GetUserError, Role},
+};
+
+/// Directory in which the data folders of all competitions are stored
+pub fn data_dir() -> anyhow::Result<PathBuf> {
+    Ok(
+        directories::ProjectDirs::from("rs", "basalt", "basalt-server")
+            .context("Failed to resolve project directory")?
+            .data_local_dir()
+            .to_path_buf(),
+    )
+}
+
+/// Path to the database file of the competition with the given title
+pub fn database_path(title: impl AsRef<str>) -> anyhow::Result<PathBuf> {
+    Ok(data_dir()?.join(title.as_ref()).join("data.db"))
+}
 
 #[derive(Debug, Deref)]
 pub struct SqliteLayer {
+    #[deref]
     db: Pool<Sqlite>,
+    /// Location of the database file, `None` for in-memory databases
+    path: Option<PathBuf>,
 }
 
 impl SqliteLayer {
@@ -31,13 +60,28 @@ impl SqliteLayer {
     /// # };
     /// ```
     pub async fn new(title: impl AsRef<str>) -> anyhow::Result<(bool, Self)> {
-        let path = directories::ProjectDirs::from("rs", "basalt", "basalt-server")
-            .context("Failed to resolve project directory")?
-            .data_local_dir()
-            .join(title.as_ref())
-            .join("data.db");
+        Self::from_path(database_path(title)?).await
+    }
+
+    /// Open an existing database without creating it or applying migrations.
+    ///
+    /// The connection is read-only, so this is safe to use on the database of a running server.
+    pub async fn open_read_only(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let opts = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(false)
+            .read_only(true);
 
-        Self::from_path(&path).await
+        debug!(?path, "Connecting to sqlite database (read-only)");
+        let db = sqlx::sqlite::SqlitePool::connect_with(opts)
+            .await
+            .context("Failed to connect to SQLiteDB")?;
+
+        Ok(Self {
+            db,
+            path: Some(path.to_path_buf()),
+        })
     }
 
     /// Create a new instance of the database at a specific path
@@ -63,7 +107,10 @@ impl SqliteLayer {
             .context("Failed to connect to SQLiteDB")?;
 
         // aways intialise, in case new tables need to be created
-        let this = Self { db };
+        let this = Self {
+            db,
+            path: Some(path.to_path_buf()),
+        };
         this.init_db().await?;
 
         Ok((init, this))
@@ -80,24 +127,66 @@ impl SqliteLayer {
             .await
             .context("Failed to connect to SQLite DB")?;
 
-        let this = Self { db };
+        let this = Self { db, path: None };
 
         this.init_db().await?;
 
         Ok(this)
     }
 
-    /// Run the migration.sql to initialise the database tables
-    async fn init_db(&self) -> anyhow::Result<()> {
-        sqlx::raw_sql(include_str!("../../migration.sql"))
+    /// Directory containing the database file, `None` for in-memory databases
+    pub fn directory(&self) -> Option<&Path> {
+        self.path.as_deref().and_then(Path::parent)
+    }
+
+    /// Write a consistent snapshot of the database to `dest`.
+    ///
+    /// This uses `VACUUM INTO`, so it is safe to call while the database is in use, and works on
+    /// read-only connections.  `dest` must not already exist.
+    pub async fn snapshot(&self, dest: impl AsRef<Path>) -> anyhow::Result<()> {
+        let dest = dest.as_ref();
+        let dest_str = dest.to_str().context("Snapshot path must be valid UTF-8")?;
+
+        debug!(?dest, "Writing database snapshot");
+        sqlx::query("VACUUM INTO ?")
+            .bind(dest_str)
             .execute(&self.db)
             .await
-            .context("Intialising Database")?;
+            .with_context(|| format!("Writing snapshot to {}", dest.display()))?;
         Ok(())
     }
 
+    /// Apply any migrations which the database is missing
+    async fn init_db(&self) -> anyhow::Result<()> {
+        migrations::migrate(&self.db)
+            .await
+            .context("Intialising Database")
+    }
+
+    /// Version of the schema that this database uses
+    pub async fn schema_version(&self) -> anyhow::Result<i64> {
+        migrations::schema_version(&self.db).await
+    }
+
+    /// Check whether the database has a table.  Databases opened with
+    /// [`SqliteLayer::open_read_only`] are not migrated, so tables added by later migrations may
+    /// be missing.
+    pub async fn has_table(&self, name: &str) -> anyhow::Result<bool> {
+        migrations::table_exists(&self.db, name).await
+    }
+
     pub async fn ingest(&self, cfg: &Config) -> anyhow::Result<()> {
         let mut tx = self.db.begin().await.unwrap();
+        competition::set_competition(
+            &mut *tx,
+            &Competition {
+                title: cfg.packet.title.clone(),
+                created_at: Utc::now(),
+            },
+        )
+        .await
+        .context("Failed to record competition")?;
+
         for user in &cfg.accounts.competitors {
             create_user(
                 &mut *tx,
@@ -128,6 +217,135 @@ impl SqliteLayer {
 
         Ok(())
     }
+
+    /// Bring the accounts in the database in line with the accounts in `cfg`.
+    ///
+    /// Accounts missing from the database are always created and previously disabled accounts
+    /// which are back in the config are enabled.  Everything else is controlled by `options`.
+    pub async fn reconcile(
+        &self,
+        cfg: &Config,
+        options: ReconcileOptions,
+    ) -> anyhow::Result<ReconcileReport> {
+        let mut report = ReconcileReport::default();
+        let mut tx = self.db.begin().await.context("Starting transaction")?;
+
+        let accounts = cfg
+            .accounts
+            .competitors
+            .iter()
+            .map(|a| (a, Role::Competitor))
+            .chain(cfg.accounts.hosts.iter().map(|a| (a, Role::Host)));
+
+        let mut seen = HashSet::new();
+        for (account, role) in accounts {
+            seen.insert(account.name.as_str());
+
+            let mut user = match users::get_user_by_username(&mut *tx, &account.name).await {
+                Ok(user) => user,
+                Err(GetUserError::UserNotFound { .. }) => {
+                    create_user(
+                        &mut *tx,
+                        &account.name,
+                        account.display_name.as_deref(),
+                        &account.password,
+                        role,
+                    )
+                    .await
+                    .with_context(|| format!("Failed to create user {}", account.name))?;
+                    report.created.push(account.name.clone());
+                    continue;
+                }
+                Err(err) => return Err(err).context("Failed to look up user"),
+            };
+
+            if users::enable_user(&mut *tx, &user.id)
+                .await
+                .context("Failed to enable user")?
+            {
+                report.enabled.push(account.name.clone());
+            }
+
+            if !options.update_existing {
+                continue;
+            }
+
+            let mut changed = false;
+            if user.display_name != account.display_name {
+                user.display_name = account.display_name.clone();
+                changed = true;
+            }
+            if user.role != role {
+                user.role = role;
+                changed = true;
+            }
+            let password_changed = !users::verify_password(&user, &account.password);
+            if password_changed {
+                user.password_hash = users::hash_password(&account.password);
+                changed = true;
+            }
+
+            if changed {
+                let id = user.id;
+                users::update_user(&mut *tx, user)
+                    .await
+                    .with_context(|| format!("Failed to update user {}", account.name))?;
+                if password_changed {
+                    session::close_user_sessions(&mut *tx, &id)
+                        .await
+                        .context("Failed to end sessions")?;
+                }
+                report.updated.push(account.name.clone());
+            }
+        }
+
+        for user in users::get_all_users(&mut *tx)
+            .await
+            .context("Failed to fetch users")?
+        {
+            if seen.contains(user.username.as_str())
+                || users::is_user_disabled(&mut *tx, &user.id)
+                    .await
+                    .context("Failed to fetch user")?
+            {
+                // still in the config, or already disabled by an earlier reconciliation
+                continue;
+            }
+            if options.disable_removed {
+                users::disable_user(&mut tx, &user.id)
+                    .await
+                    .context("Failed to disable user")?;
+            }
+            report.removed.push(user.username);
+        }
+
+        tx.commit()
+            .await
+            .context("Failed to commit reconciliation transaction")?;
+
+        Ok(report)
+    }
+}
+
+/// Controls how [`SqliteLayer::reconcile`] treats accounts which already exist in the database
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReconcileOptions {
+    /// Update the password, display name, and role of existing accounts to match the config
+    pub update_existing: bool,
+    /// Disable accounts which are no longer in the config, preventing them from logging in
+    pub disable_removed: bool,
+}
+
+/// Usernames of the accounts that were changed by [`SqliteLayer::reconcile`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReconcileReport {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    /// Accounts which were disabled, but are back in the config
+    pub enabled: Vec<String>,
+    /// Accounts which are in the database but not in the config.  These are only disabled if
+    /// [`ReconcileOptions::disable_removed`] is set.
+    pub removed: Vec<String>,
 }
 
 // just proxy methods to self.db
@@ -190,11 +408,18 @@ impl<'a> Executor<'a> for &SqliteLayer {
 #[cfg(test)]
 mod tests {
     use crate::{
-        repositories::users::{create_user, Role},
-        storage::SqliteLayer,
-        testing::{mock_db, users_repositories::get_user_by_username},
+        repositories::{
+            announcements,
+            competition::get_competition,
+            session, submissions,
+            users::{self, create_user, Role},
+        },
+        server::standings,
+        storage::{ReconcileOptions, ReconcileReport, SqliteLayer},
+        testing::{mock_db, users_repositories::get_user_by_username, SAMPLE_1},
     };
     use bedrock::Config;
+    use sqlx::sqlite::SqliteConnectOptions;
 
     #[tokio::test]
     async fn ingestion_works() {
@@ -207,6 +432,60 @@ mod tests {
         let db = mock_db().await;
 
         db.ingest(&cfg).await.expect("Failed to ingest config");
+
+        let competition = get_competition(&db).await.unwrap().unwrap();
+        assert_eq!(competition.title, cfg.packet.title);
+    }
+
+    #[tokio::test]
+    async fn reconcile_accounts() {
+        let cfg = Config::from_str(
+            include_str!("../../../samples/single.toml"),
+            Some("single.toml"),
+        )
+        .unwrap();
+
+        let db = mock_db().await;
+        create_user(&db, "team1", Some("Team One"), "old", Role::Competitor)
+            .await
+            .unwrap();
+        create_user(&db, "stale", None, "password", Role::Competitor)
+            .await
+            .unwrap();
+
+        let report = db
+            .reconcile(&cfg, ReconcileOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.created, ["team2", "host"]);
+        assert!(report.updated.is_empty());
+        assert_eq!(report.removed, ["stale"]);
+        let team1 = get_user_by_username(&db, "team1").await.unwrap();
+        assert_eq!(team1.display_name.as_deref(), Some("Team One"));
+
+        let session = session::create_session(&db, &team1).await.unwrap();
+
+        let options = ReconcileOptions {
+            update_existing: true,
+            disable_removed: true,
+        };
+        let report = db.reconcile(&cfg, options).await.unwrap();
+        // the password changed, so team1 has to log in again
+        assert!(session::get_user_from_session(&db, session.as_str())
+            .await
+            .is_err());
+        assert!(report.created.is_empty());
+        assert_eq!(report.updated, ["team1"]);
+        assert_eq!(report.removed, ["stale"]);
+        let team1 = get_user_by_username(&db, "team1").await.unwrap();
+        assert_eq!(team1.display_name, None);
+        assert!(users::verify_password(&team1, "pwd1"));
+        let stale = get_user_by_username(&db, "stale").await.unwrap();
+        assert!(users::is_user_disabled(&db, &stale.id).await.unwrap());
+
+        // nothing left to do
+        let report = db.reconcile(&cfg, options).await.unwrap();
+        assert_eq!(report, ReconcileReport::default());
     }
 
     #[tokio::test]
@@ -237,4 +516,76 @@ mod tests {
         layer.close().await;
         drop(layer);
     }
+
+    #[tokio::test]
+    async fn snapshot_database() {
+        let tempdir = async_tempfile::TempDir::new().await.unwrap();
+        let file = tempdir.join("snapshot.db");
+
+        let db = mock_db().await;
+        create_user(&db, "foo", None, "password", Role::Competitor)
+            .await
+            .unwrap();
+        db.snapshot(&file).await.unwrap();
+        assert!(db.directory().is_none());
+
+        let snapshot = SqliteLayer::open_read_only(&file).await.unwrap();
+        let user = get_user_by_username(&snapshot, "foo").await.unwrap();
+        assert_eq!(user.role, Role::Competitor);
+        assert_eq!(snapshot.directory(), file.parent());
+        snapshot.close().await;
+    }
+
+    #[tokio::test]
+    async fn read_unmigrated_database() {
+        let tempdir = async_tempfile::TempDir::new().await.unwrap();
+        let file = tempdir.join("data.db");
+
+        // as created by servers from before migrations were versioned
+        let opts = SqliteConnectOptions::new()
+            .filename(&file)
+            .create_if_missing(true);
+        let old = sqlx::SqlitePool::connect_with(opts).await.unwrap();
+        sqlx::raw_sql(include_str!("../../migrations/0001_initial.sql"))
+            .execute(&old)
+            .await
+            .unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, username, password_hash) VALUES ('a', 'team1', '');
+             INSERT INTO submission_history (id, submitter, code, question_index, language,
+                compile_result, compile_stdout, compile_stderr, compile_exit_status, test_only,
+                success)
+             VALUES ('s', 'a', '', 0, 'python3', 0, '', '', 0, false, true)",
+        )
+        .execute(&old)
+        .await
+        .unwrap();
+        old.close().await;
+
+        let db = SqliteLayer::open_read_only(&file).await.unwrap();
+        assert_eq!(db.schema_version().await.unwrap(), 0);
+        assert!(db.has_table("users").await.unwrap());
+        assert!(!db.has_table("competition").await.unwrap());
+
+        let roles = users::count_users_by_role(&db).await.unwrap();
+        assert_eq!(roles.len(), 1);
+        assert_eq!((roles[0].role, roles[0].count), (Role::Competitor, 1));
+        let counts = submissions::count_all_submissions(&db).await.unwrap();
+        assert_eq!((counts.submissions, counts.accepted), (1, 1));
+
+        // everything that is exported
+        let cfg = Config::from_str(SAMPLE_1, Some("single.toml")).unwrap();
+        let standings = standings::get_standings(&db, &cfg.game, None)
+            .await
+            .unwrap();
+        assert_eq!(standings.len(), 1);
+        assert_eq!(standings[0].username, "team1");
+        assert_eq!(
+            submissions::get_all_submissions(&db).await.unwrap().len(),
+            1
+        );
+        submissions::get_all_test_results(&db).await.unwrap();
+        announcements::get_announcements(&db).await.unwrap();
+        db.close().await;
+    }
 }
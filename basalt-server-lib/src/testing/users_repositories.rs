@@ -4,7 +4,8 @@ use argon2::{
 };
 use sqlx::SqliteExecutor;
 
-use crate::repositories::users::{GetUserError, Role, User, UserId};
+pub use crate::repositories::users::get_user_by_username;
+use crate::repositories::users::{Role, User, UserId};
 
 pub async fn dummy_user(
     db: impl SqliteExecutor<'_>,
@@ -29,18 +30,3 @@ pub async fn dummy_user(
         role_int
     ).fetch_one(db).await.expect("Failed to create user")
 }
-
-pub async fn get_user_by_username(
-    db: impl SqliteExecutor<'_>,
-    name: impl AsRef<str>,
-) -> Result<User, GetUserError> {
-    let name = name.as_ref();
-    sqlx::query_as!(User, "SELECT * from users WHERE username = $1", name)
-        .fetch_optional(db)
-        .await
-        .map_err(|e| GetUserError::QueryError(e.to_string()))?
-        .ok_or(GetUserError::UserNotFound {
-            property: "username",
-            value: name.to_string(),
-        })
-}
@@ -0,0 +1,38 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use tracing::info;
+
+use basalt_server_lib::storage::SqliteLayer;
+
+#[derive(Parser, Debug)]
+pub struct BackupArgs {
+    /// Name of the competition to back up
+    name: String,
+    /// File to which the snapshot is written.  Defaults to `<name>-<timestamp>.db` in the current
+    /// directory.
+    output: Option<PathBuf>,
+}
+
+pub async fn handle(args: BackupArgs) -> anyhow::Result<()> {
+    let path = super::existing_database(&args.name).await?;
+
+    let output = args.output.unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "{}-{}.db",
+            args.name,
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ))
+    });
+    if tokio::fs::try_exists(&output).await.unwrap_or(false) {
+        anyhow::bail!("{} already exists", output.display());
+    }
+
+    // `VACUUM INTO` works on read-only connections, so this is safe while the server is running
+    let db = SqliteLayer::open_read_only(&path).await?;
+    db.snapshot(&output).await?;
+    db.close().await;
+
+    info!(name = args.name, output = %output.display(), "Wrote backup");
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use std::{path::PathBuf, time::Instant};
+
+use anyhow::Context;
+use clap::Parser;
+use tracing::info;
+
+use basalt_server_lib::{
+    server::tester::{SolutionCheck, Tester},
+    settings::Settings,
+};
+
+#[derive(Parser, Debug)]
+pub struct CheckArgs {
+    /// Path to the configuration to check.
+    #[arg(default_value = "basalt.toml")]
+    config: PathBuf,
+    /// Path to the server settings, which declare the reference solutions to run.
+    #[arg(long, short)]
+    settings: Option<PathBuf>,
+}
+
+/// Shorten long program output so that reports stay readable
+fn excerpt(s: &str) -> String {
+    const MAX: usize = 200;
+    let s = s.trim_end();
+    if s.chars().count() > MAX {
+        format!("{}...", s.chars().take(MAX).collect::<String>())
+    } else {
+        s.to_string()
+    }
+}
+
+pub async fn handle(args: CheckArgs) -> anyhow::Result<()> {
+    let config = super::read_config(&args.config).await?;
+    println!(
+        "Configuration OK: {} problems, {} languages",
+        config.packet.problems.len(),
+        config.languages.iter().count()
+    );
+
+    let settings = match &args.settings {
+        Some(path) => Settings::read(path).await?,
+        None => Settings::default(),
+    };
+    settings.validate(&config).context("Validating settings")?;
+
+    let start = Instant::now();
+    let tester = Tester::new(&config, &settings);
+    info!(in = ?start.elapsed(), "Built test contexts");
+
+    let mut checked = 0;
+    let mut failed = 0;
+    for problem in &settings.problems {
+        let title = &config.packet.problems[problem.index].title;
+        for (language, path) in &problem.solutions {
+            checked += 1;
+            let code = tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("Reading solution {}", path.display()))?;
+
+            println!(
+                "Problem {} ({}), {} solution {}",
+                problem.index,
+                title,
+                language,
+                path.display()
+            );
+            match tester
+                .check_solution(
+                    &config,
+                    &problem.checker,
+                    problem.interactor.as_ref(),
+                    language,
+                    problem.index,
+                    &code,
+                )
+                .await?
+            {
+                SolutionCheck::MissingRunner => {
+                    failed += 1;
+                    println!("  FAIL: {} is not available for this problem", language);
+                }
+                SolutionCheck::CompileFailed { stdout, stderr } => {
+                    failed += 1;
+                    println!("  FAIL: solution failed to compile");
+                    println!("    stdout: {}", excerpt(&stdout));
+                    println!("    stderr: {}", excerpt(&stderr));
+                }
+                SolutionCheck::Ran { passed, failures } if failures.is_empty() => {
+                    println!("  OK: passed all {} tests", passed);
+                }
+                SolutionCheck::Ran { passed, failures } => {
+                    failed += 1;
+                    println!(
+                        "  FAIL: passed {} of {} tests",
+                        passed,
+                        passed as usize + failures.len()
+                    );
+                    for f in failures {
+                        println!("    test {}: {:?}", f.index, f.state);
+                        println!("      expected: {}", excerpt(&f.expected));
+                        println!("      actual:   {}", excerpt(&f.stdout));
+                        if !f.stderr.trim().is_empty() {
+                            println!("      stderr:   {}", excerpt(&f.stderr));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if checked == 0 {
+        println!("No reference solutions declared");
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} of {} reference solutions failed", failed, checked);
+    }
+
+    Ok(())
+}
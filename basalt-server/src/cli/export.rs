@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::Parser;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use basalt_server_lib::{repositories, server::standings, storage::SqliteLayer};
+
+#[derive(Parser, Debug)]
+pub struct ExportArgs {
+    /// Name of the competition to export
+    name: String,
+    /// Where to write the results.  If the path ends in `.tar`, a tarball is written, otherwise
+    /// the files are written into a directory at this path.  Defaults to `<name>-results`.
+    output: Option<PathBuf>,
+    /// Packet configuration of the competition, which determines how teams are ranked.
+    #[arg(long, short, default_value = "basalt.toml")]
+    config: PathBuf,
+}
+
+/// Render `rows` as both CSV and JSON, adding them to `files`
+fn render<T: Serialize>(
+    files: &mut Vec<(String, Vec<u8>)>,
+    name: &str,
+    rows: &[T],
+) -> anyhow::Result<()> {
+    let mut csv = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        csv.serialize(row)
+            .with_context(|| format!("Serialising {} as CSV", name))?;
+    }
+    let csv = csv
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .with_context(|| format!("Serialising {} as CSV", name))?;
+    files.push((format!("{}.csv", name), csv));
+
+    let json =
+        serde_json::to_vec_pretty(rows).with_context(|| format!("Serialising {} as JSON", name))?;
+    files.push((format!("{}.json", name), json));
+
+    Ok(())
+}
+
+pub async fn handle(args: ExportArgs) -> anyhow::Result<()> {
+    let path = super::existing_database(&args.name).await?;
+
+    // the mode of the competition is only recorded in its configuration
+    let config = super::read_config(&args.config).await?;
+    let db = SqliteLayer::open_read_only(&path).await?;
+
+    // the database is not migrated, so the competition is missing from databases made before it
+    // was recorded, in which case there is no title to check
+    let competition = if db.has_table("competition").await? {
+        repositories::competition::get_competition(&db).await?
+    } else {
+        None
+    };
+    if let Some(competition) = competition {
+        if competition.title != config.packet.title {
+            warn!(
+                competition = %competition.title,
+                config = %config.packet.title,
+                "Configuration is for a different packet than the competition"
+            );
+        }
+    }
+    // live standings only read tables from the initial schema, unlike those before the scoreboard
+    // was frozen
+    let standings = standings::get_standings(&db, &config.game, None)
+        .await
+        .context("Computing standings")?;
+    let submissions = repositories::submissions::get_all_submissions(&db).await?;
+    let test_results = repositories::submissions::get_all_test_results(&db).await?;
+    let announcements = repositories::announcements::get_announcements(&db).await?;
+    db.close().await;
+
+    let mut files = Vec::new();
+    render(&mut files, "standings", &standings)?;
+    render(&mut files, "submissions", &submissions)?;
+    render(&mut files, "test_results", &test_results)?;
+    render(&mut files, "announcements", &announcements)?;
+
+    let output = args
+        .output
+        .unwrap_or_else(|| PathBuf::from(format!("{}-results", args.name)));
+
+    if output.extension().is_some_and(|ext| ext == "tar") {
+        let file = std::fs::File::create(&output)
+            .with_context(|| format!("Creating {}", output.display()))?;
+        let mut tar = tar::Builder::new(file);
+        for (name, content) in &files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default(),
+            );
+            header.set_cksum();
+            tar.append_data(&mut header, name, content.as_slice())
+                .with_context(|| format!("Adding {} to tarball", name))?;
+        }
+        tar.finish().context("Writing tarball")?;
+    } else {
+        tokio::fs::create_dir_all(&output)
+            .await
+            .with_context(|| format!("Creating {}", output.display()))?;
+        for (name, content) in &files {
+            tokio::fs::write(output.join(name), content)
+                .await
+                .with_context(|| format!("Writing {}", name))?;
+        }
+    }
+
+    info!(
+        teams = standings.len(),
+        submissions = submissions.len(),
+        output = %output.display(),
+        "Exported competition results"
+    );
+
+    Ok(())
+}
@@ -1,5 +1,203 @@
-use anyhow::bail;
+use std::{path::Path, time::SystemTime};
+
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use tracing::warn;
+
+use basalt_server_lib::{
+    repositories::{self, competition::Competition, submissions::SubmissionCounts, users::Role},
+    storage::{self, SqliteLayer},
+};
+
+struct CompetitionInfo {
+    name: String,
+    size: u64,
+    modified: Option<SystemTime>,
+    /// `None` if the database could not be read
+    stats: Option<CompetitionStats>,
+}
+
+struct CompetitionStats {
+    /// `None` if the database was created before the competition details were recorded
+    competition: Option<Competition>,
+    competitors: i64,
+    hosts: i64,
+    submissions: SubmissionCounts,
+}
+
+async fn read_stats(path: &Path) -> anyhow::Result<CompetitionStats> {
+    let db = SqliteLayer::open_read_only(path).await?;
+
+    // databases made before the competition was recorded have not been migrated yet
+    let competition = if db.has_table("competition").await? {
+        repositories::competition::get_competition(&db)
+            .await
+            .context("Getting competition")?
+    } else {
+        None
+    };
+
+    let roles = repositories::users::count_users_by_role(&db)
+        .await
+        .context("Counting users")?;
+    let count = |role| {
+        roles
+            .iter()
+            .find(|c| c.role == role)
+            .map(|c| c.count)
+            .unwrap_or_default()
+    };
+
+    let submissions = repositories::submissions::count_all_submissions(&db)
+        .await
+        .context("Counting submissions")?;
+
+    let stats = CompetitionStats {
+        competition,
+        competitors: count(Role::Competitor),
+        hosts: count(Role::Host),
+        submissions,
+    };
+
+    db.close().await;
+    Ok(stats)
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024. && unit < UNITS.len() - 1 {
+        size /= 1024.;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
 
 pub async fn handle() -> anyhow::Result<()> {
-    bail!("not yet implemented");
+    let data_dir = storage::data_dir()?;
+
+    let mut entries = match tokio::fs::read_dir(&data_dir).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            println!("No competitions found in {}", data_dir.display());
+            return Ok(());
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("Reading {}", data_dir.display()));
+        }
+    };
+
+    let mut competitions = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .context("Reading data directory entry")?
+    {
+        let path = entry.path().join("data.db");
+        let Ok(metadata) = tokio::fs::metadata(&path).await else {
+            // not a competition folder
+            continue;
+        };
+
+        let stats = match read_stats(&path).await {
+            Ok(stats) => Some(stats),
+            Err(error) => {
+                warn!(?path, ?error, "Failed to read competition database");
+                None
+            }
+        };
+
+        competitions.push(CompetitionInfo {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            stats,
+        });
+    }
+
+    if competitions.is_empty() {
+        println!("No competitions found in {}", data_dir.display());
+        return Ok(());
+    }
+
+    // most recently used competitions first
+    competitions.sort_by(|a, b| b.modified.cmp(&a.modified));
+
+    let name_width = competitions
+        .iter()
+        .map(|c| c.name.len())
+        .max()
+        .unwrap_or_default()
+        .max("NAME".len());
+    let title_width = competitions
+        .iter()
+        .filter_map(|c| c.stats.as_ref()?.competition.as_ref())
+        .map(|c| c.title.len())
+        .max()
+        .unwrap_or_default()
+        .max("TITLE".len());
+
+    let format_time = |time: DateTime<Local>| time.format("%Y-%m-%d %H:%M:%S").to_string();
+
+    println!(
+        "{:<name_width$}  {:<title_width$}  {:<19}  {:>10}  {:<19}  {:>11}  {:>5}  {:>11}  {:>8}  {:>5}",
+        "NAME",
+        "TITLE",
+        "CREATED",
+        "SIZE",
+        "LAST MODIFIED",
+        "COMPETITORS",
+        "HOSTS",
+        "SUBMISSIONS",
+        "ACCEPTED",
+        "TESTS",
+    );
+    for c in competitions {
+        let modified = c
+            .modified
+            .map(|m| format_time(m.into()))
+            .unwrap_or_else(|| "-".into());
+
+        let (title, created) = match c.stats.as_ref().map(|s| s.competition.as_ref()) {
+            Some(Some(competition)) => (
+                competition.title.clone(),
+                format_time(competition.created_at.into()),
+            ),
+            Some(None) => ("-".into(), "-".into()),
+            None => ("?".into(), "?".into()),
+        };
+
+        let [competitors, hosts, submissions, accepted, tests] = match c.stats {
+            Some(s) => [
+                s.competitors,
+                s.hosts,
+                s.submissions.submissions,
+                s.submissions.accepted,
+                s.submissions.tests,
+            ]
+            .map(|n| n.to_string()),
+            None => std::array::from_fn(|_| "?".to_string()),
+        };
+
+        println!(
+            "{:<name_width$}  {:<title_width$}  {:<19}  {:>10}  {:<19}  {:>11}  {:>5}  {:>11}  {:>8}  {:>5}",
+            c.name,
+            title,
+            created,
+            format_size(c.size),
+            modified,
+            competitors,
+            hosts,
+            submissions,
+            accepted,
+            tests,
+        );
+    }
+
+    Ok(())
 }
@@ -1,8 +1,16 @@
+use std::path::{Path, PathBuf};
+
 use anyhow::Context;
 use clap::Parser;
 
+mod backup;
+mod check;
+mod export;
 mod list;
+mod rejudge;
+mod restore;
 mod run;
+mod users;
 
 #[derive(Parser, Debug)]
 struct Cli {
@@ -13,7 +21,59 @@ struct Cli {
 #[derive(Parser, Debug)]
 enum Command {
     Run(run::RunArgs),
+    /// List all competitions that have data stored on this machine
     List,
+    /// Write the results of a competition to CSV and JSON files
+    Export(export::ExportArgs),
+    /// Write a snapshot of a competition's database.  Safe to use while the server is running.
+    Backup(backup::BackupArgs),
+    /// Install a snapshot as a competition
+    Restore(restore::RestoreArgs),
+    /// Validate a configuration and run its reference solutions
+    Check(check::CheckArgs),
+    /// Manage the accounts of a competition without starting the server
+    Users(users::UsersArgs),
+    /// Judge submissions again against the current tests, for example after a test case has
+    /// been corrected.  Only use this while the server is stopped, since teams would not be
+    /// notified and a rejudge by the server could overwrite the results; use the server to
+    /// rejudge while it is running instead.
+    Rejudge(rejudge::RejudgeArgs),
+}
+
+/// Get the path to the database of the competition called `name`, failing if it does not exist
+async fn existing_database(name: &str) -> anyhow::Result<PathBuf> {
+    let path = basalt_server_lib::storage::database_path(name)?;
+    if !tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        anyhow::bail!(
+            "No competition named '{}' found at {}",
+            name,
+            path.display()
+        );
+    }
+    Ok(path)
+}
+
+/// Read and parse the packet configuration at `path`
+async fn read_config(path: &Path) -> anyhow::Result<bedrock::Config> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .context("Opening packet file")?;
+
+    let mut file = tokio::io::BufReader::new(file);
+
+    let file_name = path
+        .file_name()
+        .expect("call to File::open would fail if this does")
+        .to_str();
+
+    match bedrock::Config::read_async(&mut file, file_name).await {
+        Ok(config) => Ok(config),
+        Err(err @ bedrock::ConfigReadError::ReadError(_)) => Err(err)?,
+        Err(bedrock::ConfigReadError::MalformedData(err)) => {
+            eprintln!("{:?}", err);
+            anyhow::bail!("parsing config");
+        }
+    }
 }
 
 pub async fn handle_cmd() -> anyhow::Result<()> {
@@ -26,6 +86,24 @@ pub async fn handle_cmd() -> anyhow::Result<()> {
         Command::List => list::handle()
             .await
             .context("Failed to execute `list` command")?,
+        Command::Export(args) => export::handle(args)
+            .await
+            .context("Failed to execute `export` command")?,
+        Command::Backup(args) => backup::handle(args)
+            .await
+            .context("Failed to execute `backup` command")?,
+        Command::Restore(args) => restore::handle(args)
+            .await
+            .context("Failed to execute `restore` command")?,
+        Command::Check(args) => check::handle(args)
+            .await
+            .context("Failed to execute `check` command")?,
+        Command::Users(args) => users::handle(args)
+            .await
+            .context("Failed to execute `users` command")?,
+        Command::Rejudge(args) => rejudge::handle(args)
+            .await
+            .context("Failed to execute `rejudge` command")?,
     };
 
     Ok(())
@@ -0,0 +1,91 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::Context;
+use clap::{ArgGroup, Parser};
+
+use basalt_server_lib::{
+    repositories::{self, submissions::SubmissionId},
+    server::{
+        tester::{self, Rejudge},
+        AppState,
+    },
+    settings::Settings,
+    storage::SqliteLayer,
+};
+
+#[derive(Parser, Debug)]
+#[command(group(ArgGroup::new("target").required(true).args(["question", "team", "submission"])))]
+pub struct RejudgeArgs {
+    /// Name of the competition whose submissions are judged again
+    name: String,
+    /// Path to the configuration containing the corrected tests.
+    #[arg(default_value = "basalt.toml")]
+    config: PathBuf,
+    /// Path to the server settings, which decide how each problem is judged
+    #[arg(long)]
+    settings: Option<PathBuf>,
+    /// Judge every submission for the question at this index again
+    #[arg(long, short)]
+    question: Option<usize>,
+    /// Judge every submission made by the team with this username again
+    #[arg(long, short)]
+    team: Option<String>,
+    /// Judge only these submissions again.  May be given more than once.
+    #[arg(long, short)]
+    submission: Vec<SubmissionId>,
+}
+
+pub async fn handle(args: RejudgeArgs) -> anyhow::Result<()> {
+    let config = super::read_config(&args.config).await?;
+    if let Some(index) = args.question {
+        if index >= config.packet.problems.len() {
+            anyhow::bail!("The packet has no question at index {}", index);
+        }
+    }
+    let settings = match &args.settings {
+        Some(path) => Settings::read(path).await?,
+        None => Settings::default(),
+    };
+    settings.validate(&config).context("Validating settings")?;
+
+    super::existing_database(&args.name).await?;
+    let (_, db) = SqliteLayer::new(&args.name)
+        .await
+        .context("Opening competition database")?;
+
+    let target = match (args.question, args.team) {
+        (Some(index), _) => Rejudge::Question(index),
+        (_, Some(username)) => {
+            let user = repositories::users::get_user_by_username(&db, &username).await?;
+            Rejudge::Team(user.id)
+        }
+        (None, None) => Rejudge::Submissions(args.submission),
+    };
+
+    let mut state = AppState::new(db, config, None);
+    state.set_settings(settings);
+    state.init().await?;
+    let state = Arc::new(state);
+
+    let submissions = target.submissions(&state.db).await?;
+    println!("Rejudging {} submissions", submissions.len());
+    let rejudged = tester::rejudge_all(Arc::clone(&state), submissions).await;
+
+    for id in &rejudged {
+        if let Some(s) = repositories::submissions::get_submission(&state.db, *id).await? {
+            println!(
+                "{}: question {}, {:?}, passed {} of {}, score {}",
+                s.id,
+                s.question_index,
+                s.state,
+                s.passed,
+                s.passed + s.failed,
+                s.score
+            );
+        }
+    }
+    println!("Rejudged {} submissions", rejudged.len());
+
+    state.db.close().await;
+    Ok(())
+}
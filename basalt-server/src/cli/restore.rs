@@ -0,0 +1,131 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::Parser;
+use sqlx::{sqlite::SqliteConnectOptions, SqlitePool};
+use tracing::{info, warn};
+
+use basalt_server_lib::storage::{self, migrations, SqliteLayer};
+
+#[derive(Parser, Debug)]
+pub struct RestoreArgs {
+    /// Snapshot to restore, as created by `backup` or the `/competition/backup` endpoint
+    snapshot: PathBuf,
+    /// Name of the competition into which the snapshot is installed
+    name: String,
+    /// Replace the competition if it already exists.  The existing database is kept next to the
+    /// new one with a `.bak` suffix.
+    ///
+    /// The server must not be running for this competition while restoring.
+    #[arg(long, short)]
+    force: bool,
+}
+
+pub async fn handle(args: RestoreArgs) -> anyhow::Result<()> {
+    // Make sure that we're not about to install something that isn't a competition database
+    let snapshot = SqliteLayer::open_read_only(&args.snapshot)
+        .await
+        .with_context(|| format!("Opening snapshot {}", args.snapshot.display()))?;
+    let integrity: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(&snapshot)
+        .await
+        .context("Checking snapshot integrity")?;
+    if integrity != "ok" {
+        anyhow::bail!("Snapshot is corrupt: {}", integrity);
+    }
+    let has_users: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'users')",
+    )
+    .fetch_one(&snapshot)
+    .await
+    .context("Inspecting snapshot")?;
+    if !has_users {
+        anyhow::bail!("{} is not a competition database", args.snapshot.display());
+    }
+    let version = snapshot.schema_version().await?;
+    if version > migrations::SCHEMA_VERSION {
+        anyhow::bail!(
+            "Snapshot has schema version {}, which is newer than this server supports ({})",
+            version,
+            migrations::SCHEMA_VERSION
+        );
+    }
+    snapshot.close().await;
+
+    let dest = storage::database_path(&args.name)?;
+    if tokio::fs::try_exists(&dest).await.unwrap_or(false) {
+        if !args.force {
+            anyhow::bail!(
+                "Competition '{}' already exists, use --force to replace it",
+                args.name
+            );
+        }
+
+        let backup = dest.with_extension(format!(
+            "db.{}.bak",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        warn!(backup = %backup.display(), "Replacing existing competition database");
+        checkpoint(&dest).await?;
+        tokio::fs::rename(&dest, &backup)
+            .await
+            .context("Moving existing database")?;
+
+        // WAL files left next to the restored database would be replayed onto it, so they are
+        // kept with the database that they belong to instead
+        for suffix in ["-wal", "-shm"] {
+            let from = suffixed(&dest, suffix);
+            if tokio::fs::try_exists(&from).await.unwrap_or(false) {
+                tokio::fs::rename(&from, suffixed(&backup, suffix))
+                    .await
+                    .with_context(|| format!("Moving {}", from.display()))?;
+            }
+        }
+    }
+
+    if let Some(parent) = dest.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .context("Creating competition directory")?;
+    }
+    tokio::fs::copy(&args.snapshot, &dest)
+        .await
+        .context("Copying snapshot")?;
+
+    info!(name = args.name, path = %dest.display(), "Restored competition");
+    Ok(())
+}
+
+/// Path of a file that sqlite keeps next to the database at `path`, such as its write-ahead log
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Write everything in the write-ahead log of the database at `path` into the database itself,
+/// so that no changes are lost when it is moved
+async fn checkpoint(path: &Path) -> anyhow::Result<()> {
+    let opts = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(false);
+    let db = SqlitePool::connect_with(opts)
+        .await
+        .with_context(|| format!("Opening {}", path.display()))?;
+    let (busy, _, _): (i64, i64, i64) = sqlx::query_as("PRAGMA wal_checkpoint(TRUNCATE)")
+        .fetch_one(&db)
+        .await
+        .context("Checkpointing existing database")?;
+    db.close().await;
+
+    if busy != 0 {
+        anyhow::bail!(
+            "{} is in use, stop the server for this competition before restoring",
+            path.display()
+        );
+    }
+    Ok(())
+}
@@ -1,13 +1,14 @@
-use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 
 use anyhow::Context;
 use clap::Parser;
 use rand::distributions::Distribution;
-use tracing::info;
+use tracing::{error, info, warn};
 
 use basalt_server_lib::{
     server::{self, AppState},
-    storage::SqliteLayer,
+    settings::Settings,
+    storage::{ReconcileOptions, SqliteLayer},
 };
 
 #[derive(Parser, Debug)]
@@ -23,10 +24,30 @@ pub struct RunArgs {
     /// Path to the configuration to use to host the server.
     #[arg(default_value = "basalt.toml")]
     config: PathBuf,
+    /// Path to the server settings, see `samples/settings.toml`.
+    #[arg(long, short)]
+    settings: Option<PathBuf>,
     /// Directory in which files for the web client are stored -- if not specified, the web client
     /// is disabled.
     #[arg(long, short)]
     web_dir: Option<PathBuf>,
+    /// When reusing an existing database, create accounts that have been added to the config since
+    /// it was first created and report accounts that have been removed from it.
+    #[arg(long)]
+    reconcile: bool,
+    /// Update the passwords, display names, and roles of existing accounts to match the config.
+    #[arg(long, requires = "reconcile")]
+    update_accounts: bool,
+    /// Disable accounts that are no longer in the config so that they can no longer log in.
+    #[arg(long, requires = "reconcile")]
+    disable_removed: bool,
+    /// Judge submissions that were interrupted by the server stopping again, rather than marking
+    /// them as failed.
+    #[arg(long)]
+    requeue_interrupted: bool,
+    /// Number of seconds to wait for running tests and event handlers when shutting down.
+    #[arg(long, default_value_t = 30)]
+    shutdown_timeout: u64,
 }
 
 fn default_name() -> String {
@@ -39,27 +60,12 @@ fn default_name() -> String {
 
 pub async fn handle(args: RunArgs) -> anyhow::Result<()> {
     info!("Parsing packet configurations");
-
-    let file = tokio::fs::File::open(&args.config)
-        .await
-        .context("Opening packet file")?;
-
-    let mut file = tokio::io::BufReader::new(file);
-
-    let file_name = args
-        .config
-        .file_name()
-        .expect("call to File::open would fail if this does")
-        .to_str();
-
-    let config = match bedrock::Config::read_async(&mut file, file_name).await {
-        Ok(config) => config,
-        Err(err @ bedrock::ConfigReadError::ReadError(_)) => Err(err)?,
-        Err(bedrock::ConfigReadError::MalformedData(err)) => {
-            eprintln!("{:?}", err);
-            anyhow::bail!("parsing config");
-        }
+    let config = super::read_config(&args.config).await?;
+    let settings = match &args.settings {
+        Some(path) => Settings::read(path).await?,
+        None => Settings::default(),
     };
+    settings.validate(&config).context("Validating settings")?;
 
     let name = &args.name.unwrap_or_else(default_name);
     info!(name, "Creating Sqlite layer");
@@ -71,6 +77,32 @@ pub async fn handle(args: RunArgs) -> anyhow::Result<()> {
         db.ingest(&config)
             .await
             .context("Failed to ingest config data")?;
+    } else if args.reconcile {
+        let options = ReconcileOptions {
+            update_existing: args.update_accounts,
+            disable_removed: args.disable_removed,
+        };
+        let report = db
+            .reconcile(&config, options)
+            .await
+            .context("Failed to reconcile accounts")?;
+
+        info!(
+            created = ?report.created,
+            updated = ?report.updated,
+            enabled = ?report.enabled,
+            "Reconciled accounts"
+        );
+        if !report.removed.is_empty() {
+            if options.disable_removed {
+                info!(users = ?report.removed, "Disabled accounts removed from config");
+            } else {
+                warn!(
+                    users = ?report.removed,
+                    "Accounts are no longer in the config, use --disable-removed to disable them"
+                );
+            }
+        }
     }
 
     let addr: SocketAddr = format!("[::]:{}", args.port.unwrap_or(config.port))
@@ -79,18 +111,69 @@ pub async fn handle(args: RunArgs) -> anyhow::Result<()> {
     info!(?addr, "Serving via HTTP");
 
     let mut app_state = AppState::new(db, config, args.web_dir);
+    app_state.set_settings(settings);
     app_state.init().await?;
     let jset = app_state.init_hooks();
+    let state = Arc::new(app_state);
+    state
+        .recover_submissions(args.requeue_interrupted)
+        .await
+        .context("Recovering unfinished submissions")?;
+
+    tokio::spawn(server::scheduler::run(Arc::clone(&state)));
+
+    let shutdown = state.shutdown.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutting down, no longer accepting submissions");
+        shutdown.cancel();
+    });
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
     axum::serve(
         listener,
-        server::router(Arc::new(app_state)).into_make_service_with_connect_info::<SocketAddr>(),
+        server::router(Arc::clone(&state)).into_make_service_with_connect_info::<SocketAddr>(),
     )
+    .with_graceful_shutdown(state.shutdown.clone().cancelled_owned())
     .await?;
 
-    // wait for any dispatcher tasks to complete
-    jset.join_all().await;
+    state
+        .finish_shutdown(jset, Duration::from_secs(args.shutdown_timeout))
+        .await;
+
+    state.db.close().await;
+    info!("Shutdown complete");
 
     Ok(())
 }
+
+/// Resolves once the process is asked to stop, either by Ctrl-C or SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(error) = tokio::signal::ctrl_c().await {
+            error!(?error, "Failed to listen for Ctrl-C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(error) => {
+                error!(?error, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use rand::distributions::Distribution;
+
+use basalt_server_lib::{
+    repositories::{
+        self,
+        users::{CreateUserError, DeleteUserError, Role},
+    },
+    storage::SqliteLayer,
+};
+
+#[derive(Parser, Debug)]
+pub struct UsersArgs {
+    /// Name of the competition whose accounts are managed
+    name: String,
+    #[command(subcommand)]
+    command: UsersCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum UsersCommand {
+    /// Create a new account
+    Add {
+        username: String,
+        /// Password for the account.  A random password is generated and printed if omitted.
+        #[arg(long, short)]
+        password: Option<String>,
+        /// Name shown in place of the username
+        #[arg(long, short)]
+        display_name: Option<String>,
+        /// Create a host rather than a competitor
+        #[arg(long)]
+        host: bool,
+    },
+    /// Change the password of an account
+    Passwd {
+        username: String,
+        /// New password for the account.  A random password is generated and printed if omitted.
+        #[arg(long, short)]
+        password: Option<String>,
+    },
+    /// Remove an account
+    Remove {
+        username: String,
+        /// Also delete the submissions and announcements made by the account.  Without this,
+        /// accounts with any history cannot be removed.
+        #[arg(long)]
+        purge: bool,
+    },
+    /// List all accounts
+    List,
+}
+
+fn random_password() -> String {
+    rand::distributions::Alphanumeric
+        .sample_iter(rand::thread_rng())
+        .take(12)
+        .map(char::from)
+        .collect()
+}
+
+pub async fn handle(args: UsersArgs) -> anyhow::Result<()> {
+    super::existing_database(&args.name).await?;
+    let (_, db) = SqliteLayer::new(&args.name)
+        .await
+        .context("Opening competition database")?;
+
+    let result = run(&db, args.command).await;
+    db.close().await;
+    result
+}
+
+async fn run(db: &SqliteLayer, command: UsersCommand) -> anyhow::Result<()> {
+    match command {
+        UsersCommand::Add {
+            username,
+            password,
+            display_name,
+            host,
+        } => {
+            let generated = password.is_none();
+            let password = password.unwrap_or_else(random_password);
+            let role = if host { Role::Host } else { Role::Competitor };
+
+            match repositories::users::create_user(
+                db,
+                &username,
+                display_name.as_deref(),
+                &password,
+                role,
+            )
+            .await
+            {
+                Ok(_) => {}
+                Err(CreateUserError::Confict) => {
+                    anyhow::bail!("A user named '{}' already exists", username)
+                }
+                Err(CreateUserError::Other(err)) => {
+                    return Err(err).context("Creating user");
+                }
+            }
+
+            println!("Created {:?} '{}'", role, username);
+            if generated {
+                println!("Password: {}", password);
+            }
+        }
+        UsersCommand::Passwd { username, password } => {
+            let mut user = repositories::users::get_user_by_username(db, &username).await?;
+
+            let generated = password.is_none();
+            let password = password.unwrap_or_else(random_password);
+            user.password_hash = repositories::users::hash_password(&password);
+            let id = user.id;
+
+            // anybody logged in with the old password is logged out
+            let mut tx = db.begin().await.context("Starting transaction")?;
+            repositories::users::update_user(&mut *tx, user)
+                .await
+                .context("Updating password")?;
+            let sessions = repositories::session::close_user_sessions(&mut *tx, &id)
+                .await
+                .context("Ending sessions")?;
+            tx.commit().await.context("Committing transaction")?;
+
+            println!(
+                "Changed password of '{}', ending {} sessions",
+                username, sessions
+            );
+            if generated {
+                println!("Password: {}", password);
+            }
+        }
+        UsersCommand::Remove { username, purge } => {
+            let user = repositories::users::get_user_by_username(db, &username).await?;
+
+            let mut tx = db.begin().await.context("Starting transaction")?;
+            if purge {
+                let submissions =
+                    repositories::submissions::delete_user_submissions(&mut tx, &user.id).await?;
+                let announcements =
+                    repositories::announcements::delete_user_announcements(&mut *tx, &user.id)
+                        .await?;
+                println!(
+                    "Deleted {} submissions and {} announcements",
+                    submissions, announcements
+                );
+            }
+
+            match repositories::users::delete_user(&mut tx, &user.id).await {
+                Ok(_) => {}
+                Err(DeleteUserError::HasHistory) => anyhow::bail!(
+                    "'{}' has submissions or announcements, use --purge to delete them as well",
+                    username
+                ),
+                Err(err) => return Err(err).context("Deleting user"),
+            }
+            tx.commit().await.context("Committing transaction")?;
+
+            println!("Removed '{}' and ended their sessions", username);
+        }
+        UsersCommand::List => {
+            let users = repositories::users::get_all_users(db)
+                .await
+                .context("Fetching users")?;
+
+            if users.is_empty() {
+                println!("No users");
+                return Ok(());
+            }
+
+            let name_width = users
+                .iter()
+                .map(|u| u.username.len())
+                .max()
+                .unwrap_or_default()
+                .max("USERNAME".len());
+
+            println!("{:<name_width$}  {:<10}  DISPLAY NAME", "USERNAME", "ROLE");
+            for user in users {
+                println!(
+                    "{:<name_width$}  {:<10}  {}",
+                    user.username,
+                    format!("{:?}", user.role),
+                    user.display_name.as_deref().unwrap_or("-"),
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
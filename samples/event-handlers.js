@@ -41,3 +41,33 @@ export const onPause = async (event) => {
   console.log(result.status);
   console.log(await result.text());
 };
+
+export const onContestEnd = async (event) => {
+  const winner = event.standings[0];
+  if (!winner) return;
+
+  await fetch("http://localhost:8081/", {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({
+      content: `Time is up! ${winner.displayName ?? winner.username} is leading with ${winner.score} points`,
+    }),
+  });
+};
+
+export const onResolved = async (event) => {
+  const winner = event.standings[0];
+  if (!winner) return;
+
+  await fetch("http://localhost:8081/", {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({
+      content: `All results are in! ${winner.displayName ?? winner.username} wins with ${winner.score} points`,
+    }),
+  });
+};